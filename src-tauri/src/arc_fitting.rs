@@ -0,0 +1,203 @@
+use crate::pdf_converter::{ArcEntity, BezierEntity, CircleEntity, PathSegment, Point};
+use std::f64::consts::PI;
+
+// Paramètres auxquels chaque Bézier est comparée au cercle candidat
+const SAMPLE_PARAMS: [f64; 7] = [0.125, 0.25, 0.375, 0.5, 0.625, 0.75, 0.875];
+
+// Au-delà de ce rayon (relatif à la corde), la courbe est considérée comme une droite
+const MAX_RADIUS_TO_CHORD: f64 = 1000.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Circle {
+    center: Point,
+    radius: f64,
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+fn circle_through(a: Point, b: Point, c: Point) -> Option<Circle> {
+    let d = 2.0 * (a.x * (b.y - c.y) + b.x * (c.y - a.y) + c.x * (a.y - b.y));
+    if d.abs() < 1e-12 {
+        return None;
+    }
+    let a2 = a.x * a.x + a.y * a.y;
+    let b2 = b.x * b.x + b.y * b.y;
+    let c2 = c.x * c.x + c.y * c.y;
+    let center = Point {
+        x: (a2 * (b.y - c.y) + b2 * (c.y - a.y) + c2 * (a.y - b.y)) / d,
+        y: (a2 * (c.x - b.x) + b2 * (a.x - c.x) + c2 * (b.x - a.x)) / d,
+    };
+    Some(Circle { center, radius: distance(center, a) })
+}
+
+fn angle_of(circle: &Circle, p: Point) -> f64 {
+    (p.y - circle.center.y).atan2(p.x - circle.center.x)
+}
+
+// Angle balayé (signé, positif dans le sens trigonométrique) par la Bézier autour du centre
+fn sweep_of(circle: &Circle, bezier: &BezierEntity) -> f64 {
+    let mid = bezier.point_at(0.5);
+    let cross = (mid.x - bezier.p0.x) * (bezier.p3.y - mid.y) - (mid.y - bezier.p0.y) * (bezier.p3.x - mid.x);
+    let mut sweep = angle_of(circle, bezier.p3) - angle_of(circle, bezier.p0);
+    if cross >= 0.0 {
+        while sweep <= 0.0 {
+            sweep += 2.0 * PI;
+        }
+    } else {
+        while sweep >= 0.0 {
+            sweep -= 2.0 * PI;
+        }
+    }
+    sweep
+}
+
+fn fits_circle(circle: &Circle, bezier: &BezierEntity, tolerance: f64) -> bool {
+    let on_circle = |p: Point| (distance(circle.center, p) - circle.radius).abs() <= tolerance;
+    on_circle(bezier.p0) && on_circle(bezier.p3) && SAMPLE_PARAMS.iter().all(|&t| on_circle(bezier.point_at(t)))
+}
+
+fn fit_single(bezier: &BezierEntity, tolerance: f64) -> Option<Circle> {
+    let chord = distance(bezier.p0, bezier.p3);
+    let circle = circle_through(bezier.p0, bezier.point_at(0.5), bezier.p3)?;
+    if chord > 0.0 && circle.radius > chord * MAX_RADIUS_TO_CHORD {
+        return None;
+    }
+    // Une courbe dont la flèche est sous la tolérance reste une Bézier (quasi droite)
+    let sagitta = circle.radius - (circle.radius.powi(2) - (chord / 2.0).powi(2)).max(0.0).sqrt();
+    if sagitta <= tolerance && chord > 0.0 {
+        return None;
+    }
+    if fits_circle(&circle, bezier, tolerance) {
+        Some(circle)
+    } else {
+        None
+    }
+}
+
+fn connected(a: &BezierEntity, b: &BezierEntity, tolerance: f64) -> bool {
+    distance(a.p3, b.p0) <= tolerance
+}
+
+// Remplace les suites de Béziers cubiques consécutives qui décrivent un cercle ou un arc de cercle
+// (à la tolérance près, en coordonnées déjà transformées par la CTM) par des entités CIRCLE / ARC.
+pub fn recognize_arcs(segments: Vec<PathSegment>, tolerance: f64) -> Vec<PathSegment> {
+    let mut result = Vec::with_capacity(segments.len());
+    let mut iter = segments.into_iter().peekable();
+
+    while let Some(segment) = iter.next() {
+        let first = match segment {
+            PathSegment::Bezier(bezier) => bezier,
+            other => {
+                result.push(other);
+                continue;
+            }
+        };
+
+        let circle = match fit_single(&first, tolerance) {
+            Some(circle) => circle,
+            None => {
+                result.push(PathSegment::Bezier(first));
+                continue;
+            }
+        };
+
+        let mut sweep = sweep_of(&circle, &first);
+        let start = first.p0;
+        let mut last = first;
+
+        // Extension de l'arc aux Béziers suivantes qui restent sur le même cercle, dans le même sens
+        while let Some(PathSegment::Bezier(next)) = iter.peek() {
+            if !connected(&last, next, tolerance) || !fits_circle(&circle, next, tolerance) {
+                break;
+            }
+            let next_sweep = sweep_of(&circle, next);
+            if next_sweep.signum() != sweep.signum() || (sweep + next_sweep).abs() > 2.0 * PI + 1e-6 {
+                break;
+            }
+            sweep += next_sweep;
+            if let Some(PathSegment::Bezier(next)) = iter.next() {
+                last = next;
+            }
+        }
+
+        let closed = distance(start, last.p3) <= tolerance;
+        if closed && sweep.abs() > PI {
+            result.push(PathSegment::Circle(CircleEntity { center: circle.center, radius: circle.radius }));
+        } else {
+            result.push(PathSegment::Arc(ArcEntity {
+                center: circle.center,
+                radius: circle.radius,
+                start_angle: angle_of(&circle, start),
+                sweep,
+            }));
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Quart de cercle en Bézier cubique, du quadrant `quadrant` (0 à 3) au suivant, dans le sens trigonométrique
+    fn quarter(center: Point, radius: f64, quadrant: usize) -> BezierEntity {
+        let k = 0.552_284_749_8 * radius;
+        let (a0, a1) = (quadrant as f64 * PI / 2.0, (quadrant + 1) as f64 * PI / 2.0);
+        let on_circle = |a: f64| Point { x: center.x + radius * a.cos(), y: center.y + radius * a.sin() };
+        let (p0, p3) = (on_circle(a0), on_circle(a1));
+        BezierEntity {
+            p0,
+            p1: Point { x: p0.x - k * a0.sin(), y: p0.y + k * a0.cos() },
+            p2: Point { x: p3.x + k * a1.sin(), y: p3.y - k * a1.cos() },
+            p3,
+        }
+    }
+
+    fn reversed(bezier: BezierEntity) -> BezierEntity {
+        BezierEntity { p0: bezier.p3, p1: bezier.p2, p2: bezier.p1, p3: bezier.p0 }
+    }
+
+    #[test]
+    fn four_quarters_make_a_circle() {
+        let center = Point { x: 5.0, y: -3.0 };
+        let segments = (0..4).map(|q| PathSegment::Bezier(quarter(center, 10.0, q))).collect();
+        match recognize_arcs(segments, 0.01).as_slice() {
+            [PathSegment::Circle(circle)] => {
+                assert!(distance(circle.center, center) < 1e-3);
+                assert!((circle.radius - 10.0).abs() < 1e-3);
+            }
+            other => panic!("un seul cercle attendu : {other:?}"),
+        }
+    }
+
+    #[test]
+    fn wavy_curve_stays_a_bezier() {
+        let wave = BezierEntity {
+            p0: Point { x: 0.0, y: 0.0 },
+            p1: Point { x: 10.0, y: 10.0 },
+            p2: Point { x: 20.0, y: -10.0 },
+            p3: Point { x: 30.0, y: 0.0 },
+        };
+        let result = recognize_arcs(vec![PathSegment::Bezier(wave)], 0.01);
+        assert!(matches!(result.as_slice(), [PathSegment::Bezier(b)] if b.p3.x == 30.0));
+    }
+
+    #[test]
+    fn quarter_arc_angles() {
+        let center = Point { x: 0.0, y: 0.0 };
+        let arc = |bezier| match recognize_arcs(vec![PathSegment::Bezier(bezier)], 0.01).as_slice() {
+            [PathSegment::Arc(arc)] => (arc.start_angle, arc.sweep),
+            other => panic!("un seul arc attendu : {other:?}"),
+        };
+        let (start, sweep) = arc(quarter(center, 10.0, 0));
+        assert!(start.abs() < 1e-6);
+        assert!((sweep - PI / 2.0).abs() < 1e-6);
+        // Le sens de parcours est conservé par un balayage négatif
+        let (start, sweep) = arc(reversed(quarter(center, 10.0, 0)));
+        assert!((start - PI / 2.0).abs() < 1e-6);
+        assert!((sweep + PI / 2.0).abs() < 1e-6);
+    }
+}
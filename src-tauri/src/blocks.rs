@@ -0,0 +1,101 @@
+use crate::dxf_patch::Patches;
+use crate::names;
+use crate::pdf_converter::Transform;
+use dxf::entities::Entity;
+use dxf::{Block, Drawing, Handle};
+
+// Placement d'une INSERT décomposé depuis une matrice : rotation (degrés) et facteurs d'échelle,
+// négatif en Y pour une symétrie
+pub struct InsertPlacement {
+    pub rotation: f64,
+    pub x_scale: f64,
+    pub y_scale: f64,
+}
+
+// Une INSERT ne sait pas représenter un cisaillement : les axes transformés doivent rester perpendiculaires
+pub fn insert_placement(matrix: &Transform) -> Option<InsertPlacement> {
+    let x_scale = (matrix.a * matrix.a + matrix.b * matrix.b).sqrt();
+    let y_length = (matrix.c * matrix.c + matrix.d * matrix.d).sqrt();
+    if x_scale < 1e-9 || y_length < 1e-9 || (matrix.a * matrix.c + matrix.b * matrix.d).abs() > 1e-6 * x_scale * y_length {
+        return None;
+    }
+    let determinant = matrix.a * matrix.d - matrix.b * matrix.c;
+    Some(InsertPlacement {
+        rotation: matrix.b.atan2(matrix.a).to_degrees(),
+        x_scale,
+        y_scale: y_length.copysign(determinant),
+    })
+}
+
+// Nom de bloc d'après un nom de ressource (« Fm0 »), limité aux caractères admis en R12 (lettres, chiffres, `$`, `-`, `_`)
+// et rendu unique (« Fm0_2 ») ; la casse n'est pas distinguée
+pub fn block_name(resource: &[u8], taken: impl Fn(&str) -> bool) -> String {
+    let name = names::r12_name(&String::from_utf8_lossy(resource));
+    let name = if name.is_empty() { String::from("FORM") } else { name };
+    (1..)
+        .map(|n| if n == 1 { name.clone() } else { format!("{}_{}", name, n) })
+        .find(|candidate| !taken(candidate))
+        .unwrap_or(name)
+}
+
+// Entités ajoutées au dessin à partir du rang `start`, retirées de la section ENTITIES dans leur ordre
+pub fn take_entities(drawing: &mut Drawing, start: usize) -> Vec<Entity> {
+    let mut entities = Vec::new();
+    while drawing.entities().count() > start {
+        entities.extend(drawing.remove_entity(drawing.entities().count() - 1));
+    }
+    entities.reverse();
+    entities
+}
+
+// BLOCK formé d'entités déjà créées, dont les handles (et donc les hachures réservées) sont conservés ;
+// renvoie le handle de son BLOCK_RECORD
+pub fn add_block(drawing: &mut Drawing, name: &str, mut entities: Vec<Entity>, in_paper_space: bool, patches: &mut Patches) -> Option<Handle> {
+    let block = drawing.add_block(Block { name: name.to_string(), is_in_paperspace: in_paper_space, ..Default::default() }).handle;
+    let record = drawing.block_records().find(|record| record.name == name)?.handle;
+    // L'ENDBLK reçoit son propre handle, la bibliothèque reprenant celui du BLOCK
+    let end_block = drawing.header.next_available_handle;
+    drawing.header.next_available_handle = Handle(end_block.0 + 1);
+    patches.end_blocks.insert(block.0, end_block.0);
+
+    for entity in &mut entities {
+        entity.common.is_in_paper_space = in_paper_space;
+        entity.common.__owner_handle = record;
+    }
+    if let Some(block) = drawing.blocks_mut().find(|b| b.handle == block) {
+        block.__owner_handle = record;
+        block.entities = entities;
+    }
+    Some(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(a: f64, b: f64, c: f64, d: f64) -> Option<InsertPlacement> {
+        insert_placement(&Transform { a, b, c, d, e: 0.0, f: 0.0 })
+    }
+
+    #[test]
+    fn rotation_scale_and_mirror() {
+        let rotated = placement(0.0, 2.0, -2.0, 0.0).unwrap();
+        assert!((rotated.rotation - 90.0).abs() < 1e-9);
+        assert!((rotated.x_scale - 2.0).abs() < 1e-9 && (rotated.y_scale - 2.0).abs() < 1e-9);
+
+        let mirrored = placement(-1.0, 0.0, 0.0, 1.0).unwrap();
+        assert!((mirrored.rotation.abs() - 180.0).abs() < 1e-9);
+        assert!((mirrored.x_scale - 1.0).abs() < 1e-9 && (mirrored.y_scale + 1.0).abs() < 1e-9);
+
+        assert!(placement(1.0, 0.0, 1.0, 1.0).is_none());
+        assert!(placement(0.0, 0.0, 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn block_names_are_r12_safe_and_unique() {
+        assert_eq!(block_name(b"Fm0", |_| false), "Fm0");
+        assert_eq!(block_name(b"Porte d'entr\xc3\xa9e", |_| false), "Porte_d_entr_e");
+        assert_eq!(block_name(b"", |_| false), "FORM");
+        assert_eq!(block_name(b"Fm0", |name| name.eq_ignore_ascii_case("fm0")), "Fm0_2");
+    }
+}
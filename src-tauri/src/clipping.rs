@@ -0,0 +1,431 @@
+use crate::pdf_converter::{bezier_to_lines, FillRule, LineEntity, PathSegment, Point, SubPath};
+
+// Précision de discrétisation des chemins de découpage et des courbes coupées, en points PDF
+pub const CLIP_FLATTEN_TOLERANCE: f64 = 0.01;
+
+// Zone de découpage (`W` / `W*`) : polygones en coordonnées de page, combinés selon la règle de remplissage
+#[derive(Debug, Clone)]
+pub struct ClipRegion {
+    polygons: Vec<Vec<Point>>,
+    rule: FillRule,
+    min: Point,
+    max: Point,
+}
+
+impl ClipRegion {
+    pub fn from_subpaths(subpaths: &[SubPath], rule: FillRule) -> Self {
+        let mut polygons = Vec::new();
+        for subpath in subpaths {
+            let mut polygon: Vec<Point> = Vec::new();
+            for segment in &subpath.segments {
+                match segment {
+                    PathSegment::Bezier(bezier) => {
+                        polygon.extend(bezier_to_lines(bezier, CLIP_FLATTEN_TOLERANCE).iter().map(|l| l.start));
+                    }
+                    other => polygon.push(other.start()),
+                }
+            }
+            if let Some(last) = subpath.segments.last() {
+                polygon.push(last.end());
+            }
+            if polygon.len() >= 3 {
+                polygons.push(polygon);
+            }
+        }
+
+        let mut min = Point { x: f64::INFINITY, y: f64::INFINITY };
+        let mut max = Point { x: f64::NEG_INFINITY, y: f64::NEG_INFINITY };
+        for p in polygons.iter().flatten() {
+            min.x = min.x.min(p.x);
+            min.y = min.y.min(p.y);
+            max.x = max.x.max(p.x);
+            max.y = max.y.max(p.y);
+        }
+
+        ClipRegion { polygons, rule, min, max }
+    }
+
+    // Rectangle (BBox d'un Form XObject) transformé en coordonnées de page
+    pub fn from_polygon(polygon: Vec<Point>) -> Self {
+        let subpath = SubPath {
+            segments: polygon
+                .iter()
+                .zip(polygon.iter().cycle().skip(1))
+                .map(|(&start, &end)| PathSegment::Line(LineEntity { start, end }))
+                .collect(),
+            closed: true,
+        };
+        ClipRegion::from_subpaths(&[subpath], FillRule::NonZero)
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        self.polygons
+            .iter()
+            .flat_map(|polygon| polygon.iter().zip(polygon.iter().cycle().skip(1)).map(|(&a, &b)| (a, b)))
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        if p.x < self.min.x || p.x > self.max.x || p.y < self.min.y || p.y > self.max.y {
+            return false;
+        }
+        let mut winding = 0;
+        let mut crossings = 0;
+        for (a, b) in self.edges() {
+            // Un point situé sur le bord est considéré comme visible
+            if distance_to_segment(p, a, b) < 1e-6 {
+                return true;
+            }
+            if (a.y <= p.y) != (b.y <= p.y) {
+                let x = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+                if x > p.x {
+                    crossings += 1;
+                    winding += if b.y > a.y { 1 } else { -1 };
+                }
+            }
+        }
+        match self.rule {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => crossings % 2 == 1,
+        }
+    }
+
+    // Polygone unique et convexe (cadre de page, BBox, rectangle `re W n`), seul cas où une surface est découpée en polygone
+    fn convex_polygon(&self) -> Option<Vec<Point>> {
+        let [polygon] = self.polygons.as_slice() else {
+            return None;
+        };
+        let mut points = polygon.clone();
+        points.dedup_by(|b, a| distance(*a, *b) < 1e-9);
+        while points.len() > 1 && distance(points[0], points[points.len() - 1]) < 1e-9 {
+            points.pop();
+        }
+        if points.len() < 3 {
+            return None;
+        }
+        let mut sign = 0.0;
+        for i in 0..points.len() {
+            let (a, b, c) = (points[i], points[(i + 1) % points.len()], points[(i + 2) % points.len()]);
+            let turn = cross(b, c, a);
+            if turn.abs() < 1e-9 {
+                continue;
+            }
+            if turn * sign < 0.0 {
+                return None;
+            }
+            sign = turn.signum();
+        }
+        if sign == 0.0 {
+            return None;
+        }
+        // Sommets remis dans le sens trigonométrique
+        if sign < 0.0 {
+            points.reverse();
+        }
+        Some(points)
+    }
+
+    // Polygone entièrement visible : ses sommets sont dans la zone, ses bords ne croisent pas celui de la zone
+    // et aucun sommet de la zone (d'un trou, par exemple) ne tombe à l'intérieur
+    pub fn contains_polygon(&self, polygon: &[Point]) -> bool {
+        let mut crossings = Vec::new();
+        polygon.iter().all(|&p| self.contains(p))
+            && polygon.iter().zip(polygon.iter().cycle().skip(1)).all(|(&a, &b)| {
+                self.crossings(a, b, &mut crossings);
+                crossings.is_empty()
+            })
+            && !self.polygons.iter().flatten().any(|&p| strictly_inside(polygon, p))
+    }
+
+    fn outside_bounds(&self, a: Point, b: Point) -> bool {
+        a.x.max(b.x) < self.min.x || a.x.min(b.x) > self.max.x || a.y.max(b.y) < self.min.y || a.y.min(b.y) > self.max.y
+    }
+
+    // Paramètres t (0 < t < 1) où le segment [a, b] croise un bord de la zone
+    fn crossings(&self, a: Point, b: Point, out: &mut Vec<f64>) {
+        if self.outside_bounds(a, b) {
+            return;
+        }
+        let d = Point { x: b.x - a.x, y: b.y - a.y };
+        for (c, e) in self.edges() {
+            let f = Point { x: e.x - c.x, y: e.y - c.y };
+            let denom = d.x * f.y - d.y * f.x;
+            if denom.abs() < 1e-12 {
+                continue;
+            }
+            let t = ((c.x - a.x) * f.y - (c.y - a.y) * f.x) / denom;
+            let u = ((c.x - a.x) * d.y - (c.y - a.y) * d.x) / denom;
+            if t > 1e-9 && t < 1.0 - 1e-9 && (-1e-9..=1.0 + 1e-9).contains(&u) {
+                out.push(t);
+            }
+        }
+    }
+}
+
+fn distance_to_segment(p: Point, a: Point, b: Point) -> f64 {
+    let d = Point { x: b.x - a.x, y: b.y - a.y };
+    let len2 = d.x * d.x + d.y * d.y;
+    let t = if len2 > 0.0 { (((p.x - a.x) * d.x + (p.y - a.y) * d.y) / len2).clamp(0.0, 1.0) } else { 0.0 };
+    let q = lerp(a, b, t);
+    ((p.x - q.x).powi(2) + (p.y - q.y).powi(2)).sqrt()
+}
+
+// Point à l'intérieur d'un polygone (règle pair-impair), hors de son bord
+fn strictly_inside(polygon: &[Point], p: Point) -> bool {
+    let edges = || polygon.iter().zip(polygon.iter().cycle().skip(1));
+    if edges().any(|(&a, &b)| distance_to_segment(p, a, b) < 1e-6) {
+        return false;
+    }
+    edges().filter(|(a, b)| (a.y <= p.y) != (b.y <= p.y) && a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x) > p.x).count() % 2 == 1
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt()
+}
+
+// Produit vectoriel (b - a) ^ (p - a) : positif si p est à gauche de la droite orientée a → b
+fn cross(a: Point, b: Point, p: Point) -> f64 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+fn lerp(a: Point, b: Point, t: f64) -> Point {
+    Point { x: a.x + (b.x - a.x) * t, y: a.y + (b.y - a.y) * t }
+}
+
+fn inside_all(clips: &[&ClipRegion], p: Point) -> bool {
+    clips.iter().all(|clip| clip.contains(p))
+}
+
+// Portions visibles d'un segment de droite, sous forme de segments consécutifs
+fn clip_line(start: Point, end: Point, clips: &[&ClipRegion]) -> Vec<Option<LineEntity>> {
+    let mut params = vec![0.0, 1.0];
+    for clip in clips {
+        clip.crossings(start, end, &mut params);
+    }
+    params.sort_by(|a, b| a.total_cmp(b));
+    params.dedup_by(|a, b| (*a - *b).abs() < 1e-12);
+
+    params
+        .windows(2)
+        .map(|w| {
+            let mid = lerp(start, end, (w[0] + w[1]) / 2.0);
+            if inside_all(clips, mid) {
+                Some(LineEntity { start: lerp(start, end, w[0]), end: lerp(start, end, w[1]) })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// Découpe un sous-chemin par l'intersection des zones de découpage actives.
+// Renvoie les morceaux visibles ; un sous-chemin entièrement visible est conservé tel quel.
+pub fn clip_subpath(subpath: SubPath, clips: &[&ClipRegion]) -> Vec<SubPath> {
+    if clips.is_empty() {
+        return vec![subpath];
+    }
+
+    // `None` marque une portion invisible, qui interrompt le sous-chemin
+    let mut pieces: Vec<Option<PathSegment>> = Vec::new();
+    let mut fully_visible = true;
+
+    for segment in subpath.segments.iter() {
+        match segment {
+            PathSegment::Bezier(bezier) => {
+                let lines = bezier_to_lines(bezier, CLIP_FLATTEN_TOLERANCE);
+                let clipped: Vec<Vec<Option<LineEntity>>> = lines.iter().map(|l| clip_line(l.start, l.end, clips)).collect();
+                let all_inside = clipped.iter().all(|parts| parts.len() == 1 && parts[0].is_some());
+                let all_outside = clipped.iter().all(|parts| parts.iter().all(Option::is_none));
+                if all_inside {
+                    pieces.push(Some(segment.clone()));
+                } else if all_outside {
+                    fully_visible = false;
+                    pieces.push(None);
+                } else {
+                    // Courbe coupée par le bord : on conserve la partie visible discrétisée
+                    fully_visible = false;
+                    pieces.extend(clipped.into_iter().flatten().map(|part| part.map(PathSegment::Line)));
+                }
+            }
+            other => {
+                let parts = clip_line(other.start(), other.end(), clips);
+                if parts.len() == 1 && parts[0].is_some() {
+                    pieces.push(Some(other.clone()));
+                } else {
+                    fully_visible = false;
+                    pieces.extend(parts.into_iter().map(|part| part.map(PathSegment::Line)));
+                }
+            }
+        }
+    }
+
+    if fully_visible {
+        return vec![subpath];
+    }
+
+    let mut result: Vec<SubPath> = Vec::new();
+    let mut current = SubPath::default();
+    for piece in pieces {
+        match piece {
+            Some(segment) => current.segments.push(segment),
+            None => {
+                if !current.segments.is_empty() {
+                    result.push(std::mem::take(&mut current));
+                }
+            }
+        }
+    }
+    if !current.segments.is_empty() {
+        // Pour un contour fermé, le dernier morceau rejoint le premier
+        if subpath.closed && !result.is_empty() && result[0].segments.first().map(|s| s.start()) == current.segments.last().map(|s| s.end()) {
+            let first = result.remove(0);
+            current.segments.extend(first.segments);
+        }
+        result.push(current);
+    }
+    result
+}
+
+// Découpe d'un polygone par un polygone convexe orienté dans le sens trigonométrique (Sutherland-Hodgman)
+fn clip_convex(mut subject: Vec<Point>, clip: &[Point]) -> Vec<Point> {
+    for (&a, &b) in clip.iter().zip(clip.iter().cycle().skip(1)) {
+        let input = std::mem::take(&mut subject);
+        let inside = |p: Point| cross(a, b, p) >= -1e-9;
+        for (i, &current) in input.iter().enumerate() {
+            let previous = input[(i + input.len() - 1) % input.len()];
+            if inside(current) != inside(previous) {
+                let (from, to) = (cross(a, b, previous), cross(a, b, current));
+                subject.push(lerp(previous, current, from / (from - to)));
+            }
+            if inside(current) {
+                subject.push(current);
+            }
+        }
+        if subject.is_empty() {
+            break;
+        }
+    }
+    subject
+}
+
+// Surface visible d'un sous-chemin rempli : contours fermés bordant l'intersection avec les zones de découpage.
+// `clip_subpath` rouvre un contour coupé, ce qui convient au trait mais pas au remplissage (hachure).
+// Renvoie `None` si une zone n'est pas convexe : la surface n'est alors pas calculée.
+pub fn clip_fill(subpath: &SubPath, clips: &[&ClipRegion]) -> Option<Vec<SubPath>> {
+    let clip_polygons: Vec<Vec<Point>> = clips.iter().map(|clip| clip.convex_polygon()).collect::<Option<_>>()?;
+    let mut polygon: Vec<Point> = Vec::new();
+    for segment in &subpath.segments {
+        match segment {
+            PathSegment::Bezier(bezier) => polygon.extend(bezier_to_lines(bezier, CLIP_FLATTEN_TOLERANCE).iter().map(|l| l.start)),
+            other => polygon.push(other.start()),
+        }
+    }
+    for clip in &clip_polygons {
+        if polygon.len() < 3 {
+            break;
+        }
+        polygon = clip_convex(polygon, clip);
+    }
+    polygon.dedup_by(|b, a| distance(*a, *b) < 1e-9);
+    while polygon.len() > 1 && distance(polygon[0], polygon[polygon.len() - 1]) < 1e-9 {
+        polygon.pop();
+    }
+    if polygon.len() < 3 {
+        return Some(Vec::new());
+    }
+    let segments = polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(&start, &end)| PathSegment::Line(LineEntity { start, end }))
+        .collect();
+    Some(vec![SubPath { segments, closed: true }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polygon(points: &[(f64, f64)]) -> SubPath {
+        let points: Vec<Point> = points.iter().map(|&(x, y)| Point { x, y }).collect();
+        let segments = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(&start, &end)| PathSegment::Line(LineEntity { start, end }))
+            .collect();
+        SubPath { segments, closed: true }
+    }
+
+    fn vertices(subpath: &SubPath) -> Vec<(f64, f64)> {
+        let mut points: Vec<(f64, f64)> = subpath.segments.iter().map(|s| (s.start().x, s.start().y)).collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        points
+    }
+
+    #[test]
+    fn region_contains_points_on_and_inside_the_border() {
+        let clip = ClipRegion::from_polygon(vec![Point { x: 0.0, y: 0.0 }, Point { x: 10.0, y: 0.0 }, Point { x: 10.0, y: 10.0 }, Point { x: 0.0, y: 10.0 }]);
+        assert!(clip.contains(Point { x: 5.0, y: 5.0 }));
+        assert!(clip.contains(Point { x: 10.0, y: 5.0 }));
+        assert!(!clip.contains(Point { x: 11.0, y: 5.0 }));
+    }
+
+    #[test]
+    fn region_contains_polygons() {
+        let page = ClipRegion::from_subpaths(&[polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)])], FillRule::NonZero);
+        let points = |points: &[(f64, f64)]| points.iter().map(|&(x, y)| Point { x, y }).collect::<Vec<_>>();
+        assert!(page.contains_polygon(&points(&[(1.0, 1.0), (9.0, 1.0), (9.0, 9.0), (1.0, 9.0)])));
+        assert!(page.contains_polygon(&points(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)])));
+        assert!(!page.contains_polygon(&points(&[(5.0, 5.0), (15.0, 5.0), (15.0, 15.0), (5.0, 15.0)])));
+
+        // Cadre percé d'un trou (pair-impair) : les sommets sont visibles, pas le centre
+        let frame = ClipRegion::from_subpaths(
+            &[polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]), polygon(&[(4.0, 4.0), (6.0, 4.0), (6.0, 6.0), (4.0, 6.0)])],
+            FillRule::EvenOdd,
+        );
+        assert!(!frame.contains_polygon(&points(&[(1.0, 1.0), (9.0, 1.0), (9.0, 9.0), (1.0, 9.0)])));
+    }
+
+    #[test]
+    fn stroke_clipping_opens_a_crossing_contour() {
+        let clip = ClipRegion::from_subpaths(&[polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)])], FillRule::NonZero);
+        let pieces = clip_subpath(polygon(&[(5.0, 5.0), (15.0, 5.0), (15.0, 15.0), (5.0, 15.0)]), &[&clip]);
+        assert_eq!(pieces.len(), 1);
+        assert!(!pieces[0].closed);
+        assert_eq!(pieces[0].segments.len(), 2);
+
+        let inside = polygon(&[(1.0, 1.0), (2.0, 1.0), (2.0, 2.0)]);
+        let pieces = clip_subpath(inside, &[&clip]);
+        assert!(matches!(pieces.as_slice(), [piece] if piece.closed && piece.segments.len() == 3));
+    }
+
+    #[test]
+    fn fill_clipping_closes_the_area_along_the_clip() {
+        let page = ClipRegion::from_subpaths(&[polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)])], FillRule::NonZero);
+        // Sens horaire et triangle : l'orientation des polygones est indifférente
+        let window = ClipRegion::from_subpaths(&[polygon(&[(0.0, 0.0), (0.0, 20.0), (20.0, 0.0)])], FillRule::NonZero);
+        let bleed = polygon(&[(-5.0, -5.0), (15.0, -5.0), (15.0, 15.0), (-5.0, 15.0)]);
+
+        let area = clip_fill(&bleed, &[&page]).unwrap();
+        assert_eq!(area.len(), 1);
+        assert!(area[0].closed);
+        assert_eq!(vertices(&area[0]), vec![(0.0, 0.0), (0.0, 10.0), (10.0, 0.0), (10.0, 10.0)]);
+
+        let area = clip_fill(&bleed, &[&page, &window]).unwrap();
+        assert_eq!(vertices(&area[0]), vec![(0.0, 0.0), (0.0, 10.0), (10.0, 0.0), (10.0, 10.0)]);
+
+        let outside = polygon(&[(20.0, 20.0), (30.0, 20.0), (30.0, 30.0)]);
+        assert!(clip_fill(&outside, &[&page]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fill_clipping_needs_convex_regions() {
+        let notch = ClipRegion::from_subpaths(&[polygon(&[(0.0, 0.0), (10.0, 0.0), (5.0, 5.0), (10.0, 10.0), (0.0, 10.0)])], FillRule::NonZero);
+        let square = polygon(&[(-1.0, -1.0), (11.0, -1.0), (11.0, 11.0), (-1.0, 11.0)]);
+        assert!(clip_fill(&square, &[&notch]).is_none());
+
+        let two_rectangles = ClipRegion::from_subpaths(
+            &[polygon(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]), polygon(&[(2.0, 0.0), (3.0, 0.0), (3.0, 1.0), (2.0, 1.0)])],
+            FillRule::NonZero,
+        );
+        assert!(clip_fill(&square, &[&two_rectangles]).is_none());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::pdf_objects::{as_f64, resolve};
+use lopdf::{Dictionary, Document, Object};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+
+    fn from_unit(r: f64, g: f64, b: f64) -> Self {
+        let to_u8 = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Rgb { r: to_u8(r), g: to_u8(g), b: to_u8(b) }
+    }
+
+    // Valeur du groupe 420 (couleur vraie)
+    pub fn true_color(&self) -> i32 {
+        ((self.r as i32) << 16) | ((self.g as i32) << 8) | self.b as i32
+    }
+}
+
+// --- Espaces colorimétriques PDF ---
+
+#[derive(Debug, Clone)]
+pub enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Lab,
+    Indexed { base: Box<ColorSpace>, hival: usize, lookup: Vec<u8> },
+    // Separation / DeviceN : teinte convertie par une fonction exponentielle, ou en niveaux de gris à défaut
+    Tint { base: Box<ColorSpace>, c0: Vec<f64>, c1: Vec<f64>, exponent: f64, exact: bool },
+    Pattern,
+}
+
+impl ColorSpace {
+    pub fn component_count(&self) -> usize {
+        match self {
+            ColorSpace::Gray | ColorSpace::Indexed { .. } | ColorSpace::Pattern => 1,
+            ColorSpace::Rgb | ColorSpace::Lab => 3,
+            ColorSpace::Cmyk => 4,
+            ColorSpace::Tint { c0, .. } => c0.len().max(1),
+        }
+    }
+
+    // Couleur initiale définie par `CS` / `cs` (noir, ou teinte maximale)
+    pub fn initial_components(&self) -> Vec<f64> {
+        match self {
+            ColorSpace::Cmyk => vec![0.0, 0.0, 0.0, 1.0],
+            ColorSpace::Tint { .. } => vec![1.0],
+            ColorSpace::Lab => vec![0.0, 0.0, 0.0],
+            other => vec![0.0; other.component_count()],
+        }
+    }
+
+    // Espace désigné par l'opérande de `CS` / `cs` : nom d'espace de base ou entrée de /ColorSpace
+    pub fn from_name(doc: &Document, resources: Option<&Dictionary>, name: &[u8]) -> ColorSpace {
+        match name {
+            b"DeviceGray" | b"G" | b"CalGray" => ColorSpace::Gray,
+            b"DeviceRGB" | b"RGB" | b"CalRGB" => ColorSpace::Rgb,
+            b"DeviceCMYK" | b"CMYK" => ColorSpace::Cmyk,
+            b"Pattern" => ColorSpace::Pattern,
+            _ => resources
+                .and_then(|res| res.get(b"ColorSpace").ok())
+                .and_then(|cs| resolve(doc, cs).as_dict().ok())
+                .and_then(|dict| dict.get(name).ok())
+                .map(|obj| ColorSpace::from_object(doc, obj))
+                .unwrap_or(ColorSpace::Gray),
+        }
+    }
+
+    pub fn from_object(doc: &Document, obj: &Object) -> ColorSpace {
+        let obj = resolve(doc, obj);
+        if let Ok(name) = obj.as_name() {
+            return ColorSpace::from_name(doc, None, name);
+        }
+        let array = match obj.as_array() {
+            Ok(array) if !array.is_empty() => array,
+            _ => return ColorSpace::Gray,
+        };
+        let family = resolve(doc, &array[0]).as_name().unwrap_or(b"");
+        match family {
+            b"CalGray" => ColorSpace::Gray,
+            b"CalRGB" => ColorSpace::Rgb,
+            b"Lab" => ColorSpace::Lab,
+            b"Pattern" => ColorSpace::Pattern,
+            b"ICCBased" => {
+                // L'espace ICC est approché par l'espace de base de même nombre de composantes
+                let n = array
+                    .get(1)
+                    .and_then(|o| resolve(doc, o).as_stream().ok())
+                    .and_then(|stream| stream.dict.get(b"N").and_then(|n| n.as_i64()).ok())
+                    .unwrap_or(3);
+                match n {
+                    1 => ColorSpace::Gray,
+                    4 => ColorSpace::Cmyk,
+                    _ => ColorSpace::Rgb,
+                }
+            }
+            b"Indexed" | b"I" if array.len() >= 4 => {
+                let base = ColorSpace::from_object(doc, &array[1]);
+                let hival = as_f64(resolve(doc, &array[2])).max(0.0) as usize;
+                let lookup = match resolve(doc, &array[3]) {
+                    Object::String(bytes, _) => bytes.clone(),
+                    Object::Stream(stream) => stream.decompressed_content().unwrap_or_else(|_| stream.content.clone()),
+                    _ => Vec::new(),
+                };
+                ColorSpace::Indexed { base: Box::new(base), hival, lookup }
+            }
+            b"Separation" | b"DeviceN" if array.len() >= 4 => {
+                let components = match family {
+                    b"DeviceN" => resolve(doc, &array[1]).as_array().map(|a| a.len()).unwrap_or(1),
+                    _ => 1,
+                };
+                let base = ColorSpace::from_object(doc, &array[2]);
+                let function = resolve(doc, &array[3]);
+                let function_dict = function.as_dict().ok().or_else(|| function.as_stream().ok().map(|s| &s.dict));
+                let exponential = function_dict.filter(|dict| dict.get(b"FunctionType").and_then(|t| t.as_i64()).ok() == Some(2));
+                match exponential {
+                    Some(dict) if components == 1 => {
+                        let read = |key: &[u8], default: f64| -> Vec<f64> {
+                            dict.get(key)
+                                .ok()
+                                .and_then(|o| resolve(doc, o).as_array().ok())
+                                .map(|a| a.iter().map(|v| as_f64(resolve(doc, v))).collect())
+                                .unwrap_or_else(|| vec![default])
+                        };
+                        ColorSpace::Tint {
+                            base: Box::new(base),
+                            c0: read(b"C0", 0.0),
+                            c1: read(b"C1", 1.0),
+                            exponent: dict.get(b"N").map(|n| as_f64(resolve(doc, n))).unwrap_or(1.0),
+                            exact: true,
+                        }
+                    }
+                    _ => ColorSpace::Tint {
+                        base: Box::new(base),
+                        c0: vec![0.0; components],
+                        c1: vec![1.0; components],
+                        exponent: 1.0,
+                        exact: false,
+                    },
+                }
+            }
+            _ => ColorSpace::Gray,
+        }
+    }
+
+    pub fn to_rgb(&self, components: &[f64]) -> Option<Rgb> {
+        let c = |i: usize| components.get(i).copied().unwrap_or(0.0);
+        match self {
+            ColorSpace::Gray => Some(Rgb::from_unit(c(0), c(0), c(0))),
+            ColorSpace::Rgb => Some(Rgb::from_unit(c(0), c(1), c(2))),
+            ColorSpace::Cmyk => Some(Rgb::from_unit(
+                (1.0 - c(0)) * (1.0 - c(3)),
+                (1.0 - c(1)) * (1.0 - c(3)),
+                (1.0 - c(2)) * (1.0 - c(3)),
+            )),
+            ColorSpace::Lab => Some(lab_to_rgb(c(0), c(1), c(2))),
+            ColorSpace::Indexed { base, hival, lookup } => {
+                let index = (c(0).round().max(0.0) as usize).min(*hival);
+                let n = base.component_count();
+                let start = index.checked_mul(n)?;
+                let entry = lookup.get(start..start.checked_add(n)?)?;
+                let base_components: Vec<f64> = entry.iter().map(|&v| v as f64 / 255.0).collect();
+                base.to_rgb(&base_components)
+            }
+            ColorSpace::Tint { base, c0, c1, exponent, exact } => {
+                if *exact {
+                    let t = c(0).clamp(0.0, 1.0).powf(*exponent);
+                    let base_components: Vec<f64> = c0.iter().zip(c1.iter()).map(|(a, b)| a + t * (b - a)).collect();
+                    base.to_rgb(&base_components)
+                } else {
+                    // Fonction de teinte non gérée : teinte maximale = noir
+                    let tint = (0..components.len().max(1)).map(c).fold(0.0, f64::max);
+                    let v = 1.0 - tint.clamp(0.0, 1.0);
+                    Some(Rgb::from_unit(v, v, v))
+                }
+            }
+            ColorSpace::Pattern => None,
+        }
+    }
+}
+
+// CIE L*a*b* (D50) vers sRGB
+fn lab_to_rgb(l: f64, a: f64, b: f64) -> Rgb {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+    let f_inv = |t: f64| if t > 6.0 / 29.0 { t * t * t } else { 3.0 * (6.0f64 / 29.0).powi(2) * (t - 4.0 / 29.0) };
+    let (x, y, z) = (0.9642 * f_inv(fx), f_inv(fy), 0.8249 * f_inv(fz));
+    let r = 3.1339 * x - 1.6169 * y - 0.4906 * z;
+    let g = -0.9788 * x + 1.9161 * y + 0.0335 * z;
+    let bl = 0.0719 * x - 0.2290 * y + 1.4052 * z;
+    let gamma = |v: f64| if v <= 0.0031308 { 12.92 * v } else { 1.055 * v.max(0.0).powf(1.0 / 2.4) - 0.055 };
+    Rgb::from_unit(gamma(r), gamma(g), gamma(bl))
+}
+
+// --- Palette AutoCAD (ACI) ---
+
+fn aci_palette_entry(index: u8) -> Rgb {
+    let rgb = |r, g, b| Rgb { r, g, b };
+    match index {
+        1 => rgb(255, 0, 0),
+        2 => rgb(255, 255, 0),
+        3 => rgb(0, 255, 0),
+        4 => rgb(0, 255, 255),
+        5 => rgb(0, 0, 255),
+        6 => rgb(255, 0, 255),
+        7 => rgb(255, 255, 255),
+        8 => rgb(128, 128, 128),
+        9 => rgb(192, 192, 192),
+        250..=255 => {
+            let v = [51, 91, 132, 173, 214, 255][(index - 250) as usize];
+            rgb(v, v, v)
+        }
+        10..=249 => {
+            // 24 teintes espacées de 15°, 5 niveaux de luminosité, chacun en version saturée et pâle
+            let hue = ((index / 10) - 1) as f64 * 15.0;
+            let shade = index % 10;
+            let sector = hue / 60.0;
+            let frac = sector - sector.floor();
+            let ramp = |f: f64| (255.0 * f).floor();
+            let (r, g, b) = match sector.floor() as u32 {
+                0 => (255.0, ramp(frac), 0.0),
+                1 => (ramp(1.0 - frac), 255.0, 0.0),
+                2 => (0.0, 255.0, ramp(frac)),
+                3 => (0.0, ramp(1.0 - frac), 255.0),
+                4 => (ramp(frac), 0.0, 255.0),
+                _ => (255.0, 0.0, ramp(1.0 - frac)),
+            };
+            let pale = |v: f64| if shade % 2 == 1 { v + ((255.0 - v) / 2.0).floor() } else { v };
+            let value = [255.0, 165.0, 127.0, 76.0, 38.0][(shade / 2) as usize];
+            let scale = |v: f64| (pale(v) * value / 255.0).round() as u8;
+            rgb(scale(r), scale(g), scale(b))
+        }
+        _ => rgb(0, 0, 0),
+    }
+}
+
+// Index ACI le plus proche ; le noir et le blanc donnent 7 (blanc sur fond noir, noir sur fond blanc)
+pub fn nearest_aci(color: Rgb) -> u8 {
+    let max = color.r.max(color.g).max(color.b);
+    let min = color.r.min(color.g).min(color.b);
+    if max <= 24 || min >= 240 {
+        return 7;
+    }
+
+    let mut best = 7;
+    let mut best_distance = i32::MAX;
+    for index in 1..=255u8 {
+        if index == 7 {
+            continue;
+        }
+        let entry = aci_palette_entry(index);
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        let distance = d(entry.r, color.r) + d(entry.g, color.g) + d(entry.b, color.b);
+        if distance < best_distance {
+            best_distance = distance;
+            best = index;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgb = Rgb { r: 255, g: 0, b: 0 };
+    const BLUE: Rgb = Rgb { r: 0, g: 0, b: 255 };
+
+    #[test]
+    fn nearest_aci_of_common_colors() {
+        assert_eq!(nearest_aci(RED), 1);
+        assert_eq!(nearest_aci(BLUE), 5);
+        assert_eq!(nearest_aci(Rgb { r: 128, g: 128, b: 128 }), 8);
+        // Le noir, le blanc et les gris très proches restent en 7
+        assert_eq!(nearest_aci(Rgb::BLACK), 7);
+        assert_eq!(nearest_aci(Rgb { r: 250, g: 250, b: 250 }), 7);
+        assert_eq!(nearest_aci(Rgb { r: 10, g: 20, b: 5 }), 7);
+        // Un rouge sombre tombe sur une teinte rouge de la palette, hors couleurs standard
+        assert_eq!(nearest_aci(Rgb { r: 127, g: 0, b: 0 }), 14);
+    }
+
+    #[test]
+    fn device_spaces_to_rgb() {
+        assert_eq!(ColorSpace::Gray.to_rgb(&[0.5]), Some(Rgb { r: 128, g: 128, b: 128 }));
+        assert_eq!(ColorSpace::Rgb.to_rgb(&[1.0, 0.0, 0.0]), Some(RED));
+        assert_eq!(ColorSpace::Cmyk.to_rgb(&[0.0, 1.0, 1.0, 0.0]), Some(RED));
+        assert_eq!(ColorSpace::Cmyk.to_rgb(&[0.0, 0.0, 0.0, 1.0]), Some(Rgb::BLACK));
+        assert_eq!(ColorSpace::Lab.to_rgb(&[100.0, 0.0, 0.0]), Some(Rgb { r: 255, g: 255, b: 255 }));
+        assert_eq!(ColorSpace::Pattern.to_rgb(&[]), None);
+    }
+
+    #[test]
+    fn indexed_and_tint_spaces_to_rgb() {
+        let indexed = ColorSpace::Indexed { base: Box::new(ColorSpace::Rgb), hival: 1, lookup: vec![255, 0, 0, 0, 0, 255] };
+        assert_eq!(indexed.to_rgb(&[0.0]), Some(RED));
+        assert_eq!(indexed.to_rgb(&[1.0]), Some(BLUE));
+        // Un index hors palette est ramené à hival
+        assert_eq!(indexed.to_rgb(&[7.0]), Some(BLUE));
+        // Index et hival démesurés : pas de dépassement, la couleur est simplement introuvable
+        let unbounded = ColorSpace::Indexed { base: Box::new(ColorSpace::Cmyk), hival: usize::MAX, lookup: vec![0; 4] };
+        assert_eq!(unbounded.to_rgb(&[1e300]), None);
+        assert_eq!(unbounded.to_rgb(&[0.0]), Some(Rgb { r: 255, g: 255, b: 255 }));
+
+        let spot = ColorSpace::Tint { base: Box::new(ColorSpace::Cmyk), c0: vec![0.0; 4], c1: vec![0.0, 1.0, 1.0, 0.0], exponent: 1.0, exact: true };
+        assert_eq!(spot.to_rgb(&[1.0]), Some(RED));
+        assert_eq!(spot.to_rgb(&[0.0]), Some(Rgb { r: 255, g: 255, b: 255 }));
+        // Sans fonction de teinte exploitable, la teinte est rendue en gris
+        let unknown = ColorSpace::Tint { base: Box::new(ColorSpace::Cmyk), c0: vec![0.0], c1: vec![1.0], exponent: 1.0, exact: false };
+        assert_eq!(unknown.to_rgb(&[0.25]), Some(Rgb { r: 191, g: 191, b: 191 }));
+    }
+
+    #[test]
+    fn indexed_space_from_pdf_array() {
+        let doc = Document::with_version("1.5");
+        let array = Object::Array(vec![
+            Object::Name(b"Indexed".to_vec()),
+            Object::Name(b"DeviceRGB".to_vec()),
+            1.into(),
+            Object::String(vec![255, 0, 0, 0, 0, 255], lopdf::StringFormat::Hexadecimal),
+        ]);
+        let space = ColorSpace::from_object(&doc, &array);
+        assert_eq!(space.component_count(), 1);
+        assert_eq!(space.to_rgb(&[1.0]), Some(BLUE));
+    }
+}
@@ -0,0 +1,78 @@
+use dxf::CodePair;
+use std::collections::HashMap;
+
+// Retouches du fichier texte produit par la bibliothèque `dxf`, pour ce qu'elle n'écrit pas ou écrit mal :
+// - les HATCH, réservées par un POINT au même rang (handle, calque, couleur) ;
+// - les LAYOUT, écrites sans sous-classe AcDbPlotSettings et sans leur bloc d'espace papier (groupe 330) ;
+// - les ENDBLK, qui reprennent le handle de leur BLOCK.
+#[derive(Default)]
+pub struct Patches {
+    // Groupes de la hachure (à partir de AcDbHatch), par handle du POINT
+    pub hatches: HashMap<u64, Vec<CodePair>>,
+    // Groupes de la présentation (à partir de AcDbPlotSettings), par handle du LAYOUT
+    pub layouts: HashMap<u64, Vec<CodePair>>,
+    // Handle propre de l'ENDBLK, par handle du BLOCK
+    pub end_blocks: HashMap<u64, u64>,
+}
+
+impl Patches {
+    pub fn is_empty(&self) -> bool {
+        self.hatches.is_empty() && self.layouts.is_empty() && self.end_blocks.is_empty()
+    }
+
+    pub fn apply(&self, dxf_text: &str) -> String {
+        let lines: Vec<&str> = dxf_text.split("\r\n").collect();
+        let mut out = String::with_capacity(dxf_text.len());
+        let mut push = |code: &str, value: &str| {
+            out.push_str(code);
+            out.push_str("\r\n");
+            out.push_str(value);
+            out.push_str("\r\n");
+        };
+
+        let mut i = 0;
+        while i + 1 < lines.len() {
+            let handle = (lines[i].trim() == "0" && lines.get(i + 2).map(|c| c.trim()) == Some("5"))
+                .then(|| lines.get(i + 3).and_then(|h| u64::from_str_radix(h.trim(), 16).ok()))
+                .flatten();
+            let record = handle.and_then(|handle| match lines[i + 1] {
+                "POINT" => self.hatches.get(&handle).map(|pairs| ("HATCH", "AcDbPoint", pairs)),
+                "LAYOUT" => self.layouts.get(&handle).map(|pairs| ("LAYOUT", "AcDbLayout", pairs)),
+                _ => None,
+            });
+
+            if let (Some(handle), "ENDBLK") = (handle, lines[i + 1]) {
+                if let Some(&end_handle) = self.end_blocks.get(&handle) {
+                    push(lines[i], lines[i + 1]);
+                    push(lines[i + 2], &format!("{:X}", end_handle));
+                    i += 4;
+                    continue;
+                }
+            }
+            let Some((record_type, subclass, pairs)) = record else {
+                push(lines[i], lines[i + 1]);
+                i += 2;
+                continue;
+            };
+
+            push(lines[i], record_type);
+            i += 2;
+            // Groupes communs (handle, propriétaire, calque, couleur...) conservés jusqu'à la sous-classe propre
+            while i + 1 < lines.len() && !(lines[i].trim() == "100" && lines[i + 1] == subclass) {
+                push(lines[i], lines[i + 1]);
+                i += 2;
+            }
+            while i + 1 < lines.len() && lines[i].trim() != "0" {
+                i += 2;
+            }
+            for pair in pairs {
+                push(&format!("{: >3}", pair.code), &pair.value.to_string());
+            }
+        }
+        // Dernière ligne vide après le EOF final
+        if i < lines.len() {
+            out.push_str(&lines[i..].join("\r\n"));
+        }
+        out
+    }
+}
@@ -0,0 +1,643 @@
+use crate::glyph_outlines::{self, FontProgram};
+use crate::pdf_converter::SubPath;
+use crate::pdf_objects::{number, resolve};
+use lopdf::{Dictionary, Document, Object};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Chasse supposée d'un glyphe (en fraction du corps) pour les polices sans métriques (14 polices standard)
+const DEFAULT_GLYPH_WIDTH: f64 = 0.5;
+
+// Glyphe d'une chaîne de texte : texte Unicode et chasse en fraction du corps
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    pub code: u32,
+    pub text: String,
+    pub width: f64,
+    // Le `Tw` ne s'applique qu'au code 32 sur un octet
+    pub is_space: bool,
+}
+
+// Plage de codes d'une CMap (`begincodespacerange`) : longueur en octets et bornes
+#[derive(Debug, Clone, Copy)]
+struct CodeSpace {
+    length: usize,
+    low: u32,
+    high: u32,
+}
+
+// Police désignée par `Tf` : découpage des chaînes en codes, décodage en Unicode et chasses
+#[derive(Debug)]
+pub struct Font {
+    // Police composite (Type0) : codes de un à quatre octets, CID = code (Identity-H / Identity-V)
+    composite: bool,
+    code_spaces: Vec<CodeSpace>,
+    // Table ToUnicode, prioritaire sur l'encodage
+    to_unicode: HashMap<u32, String>,
+    // Encodage des polices simples : encodage de base modifié par /Differences
+    encoding: Vec<Option<String>>,
+    widths: HashMap<u32, f64>,
+    default_width: f64,
+    // Noms de glyphes des /Differences, pour retrouver le glyphe dans le programme de police
+    glyph_names: HashMap<u32, String>,
+    program: Option<FontProgram>,
+    cid_to_gid: Option<Vec<u16>>,
+    // Contours déjà calculés, par code
+    outlines: RefCell<HashMap<u32, Option<Rc<Vec<SubPath>>>>>,
+}
+
+fn stream_data(doc: &Document, obj: &Object) -> Option<Vec<u8>> {
+    let stream = resolve(doc, obj).as_stream().ok()?;
+    Some(stream.decompressed_content().unwrap_or_else(|_| stream.content.clone()))
+}
+
+impl Font {
+    pub fn load(doc: &Document, resources: Option<&Dictionary>, name: &[u8]) -> Option<Font> {
+        let fonts = resources?.get(b"Font").ok().map(|o| resolve(doc, o))?.as_dict().ok()?;
+        let dict = resolve(doc, fonts.get(name).ok()?).as_dict().ok()?;
+        Some(Font::from_dict(doc, dict))
+    }
+
+    fn from_dict(doc: &Document, dict: &Dictionary) -> Font {
+        let subtype = dict.get(b"Subtype").and_then(|o| o.as_name()).unwrap_or(b"");
+        let composite = subtype == b"Type0";
+
+        let mut font = Font {
+            composite,
+            code_spaces: Vec::new(),
+            to_unicode: HashMap::new(),
+            encoding: Vec::new(),
+            widths: HashMap::new(),
+            default_width: DEFAULT_GLYPH_WIDTH,
+            glyph_names: HashMap::new(),
+            program: None,
+            cid_to_gid: None,
+            outlines: RefCell::new(HashMap::new()),
+        };
+
+        if let Some(data) = dict.get(b"ToUnicode").ok().and_then(|o| stream_data(doc, o)) {
+            let cmap = CMap::parse(&data);
+            font.to_unicode = cmap.mappings;
+            font.code_spaces = cmap.code_spaces;
+        }
+
+        if composite {
+            // Une CMap d'encodage incorporée fixe la longueur des codes ; les CMaps nommées sont traitées en 2 octets
+            if let Some(data) = dict.get(b"Encoding").ok().and_then(|o| stream_data(doc, o)) {
+                let spaces = CMap::parse(&data).code_spaces;
+                if !spaces.is_empty() {
+                    font.code_spaces = spaces;
+                }
+            } else {
+                font.code_spaces = vec![CodeSpace { length: 2, low: 0, high: 0xFFFF }];
+            }
+            let descendant = dict
+                .get(b"DescendantFonts")
+                .ok()
+                .and_then(|o| resolve(doc, o).as_array().ok())
+                .and_then(|a| a.first())
+                .and_then(|o| resolve(doc, o).as_dict().ok());
+            if let Some(descendant) = descendant {
+                font.read_cid_widths(doc, descendant);
+                font.program = font_descriptor(doc, descendant).and_then(|d| FontProgram::load(doc, d));
+                font.cid_to_gid = glyph_outlines::cid_to_gid_map(doc, descendant);
+            }
+        } else {
+            font.code_spaces = vec![CodeSpace { length: 1, low: 0, high: 0xFF }];
+            let (encoding, glyph_names) = simple_encoding(doc, dict);
+            font.encoding = encoding;
+            font.glyph_names = glyph_names;
+            font.read_simple_widths(doc, dict, subtype == b"Type3");
+            font.program = font_descriptor(doc, dict).and_then(|d| FontProgram::load(doc, d));
+        }
+
+        font
+    }
+
+    // /FirstChar + /Widths, en millièmes de corps (ou via /FontMatrix pour les polices Type3)
+    fn read_simple_widths(&mut self, doc: &Document, dict: &Dictionary, type3: bool) {
+        let scale = if type3 {
+            dict.get(b"FontMatrix")
+                .ok()
+                .and_then(|o| resolve(doc, o).as_array().ok())
+                .and_then(|m| m.first().and_then(number))
+                .unwrap_or(0.001)
+        } else {
+            0.001
+        };
+        let first_char = dict.get(b"FirstChar").ok().and_then(|o| resolve(doc, o).as_i64().ok()).unwrap_or(0);
+        if let Ok(widths) = dict.get(b"Widths").and_then(|o| resolve(doc, o).as_array()) {
+            for (i, w) in widths.iter().enumerate() {
+                let code = i64::try_from(i).ok().and_then(|i| first_char.checked_add(i)).and_then(|c| u32::try_from(c).ok());
+                if let (Some(code), Some(w)) = (code, number(resolve(doc, w))) {
+                    self.widths.insert(code, w * scale);
+                }
+            }
+        }
+        let missing_width = font_descriptor(doc, dict)
+            .and_then(|d| d.get(b"MissingWidth").ok())
+            .and_then(|o| number(resolve(doc, o)));
+        if let Some(missing_width) = missing_width {
+            self.default_width = missing_width * scale;
+        }
+    }
+
+    // /DW et /W de la police descendante : `c [w1 w2 ...]` ou `c_premier c_dernier w`
+    fn read_cid_widths(&mut self, doc: &Document, descendant: &Dictionary) {
+        self.default_width = descendant.get(b"DW").ok().and_then(|o| number(resolve(doc, o))).unwrap_or(1000.0) / 1000.0;
+        let w = match descendant.get(b"W").and_then(|o| resolve(doc, o).as_array()) {
+            Ok(w) => w,
+            Err(_) => return,
+        };
+        let mut i = 0;
+        while i + 1 < w.len() {
+            let first = number(resolve(doc, &w[i])).unwrap_or(0.0) as u32;
+            match resolve(doc, &w[i + 1]) {
+                Object::Array(list) => {
+                    for (offset, width) in list.iter().enumerate() {
+                        // Les CID au-delà de u32::MAX sont ignorés
+                        let cid = u32::try_from(offset).ok().and_then(|offset| first.checked_add(offset));
+                        if let (Some(cid), Some(width)) = (cid, number(resolve(doc, width))) {
+                            self.widths.insert(cid, width / 1000.0);
+                        }
+                    }
+                    i += 2;
+                }
+                last => {
+                    let last = number(last).unwrap_or(0.0) as u32;
+                    if let Some(width) = w.get(i + 2).and_then(|o| number(resolve(doc, o))) {
+                        for cid in first..=last.min(first.saturating_add(0xFFFF)) {
+                            self.widths.insert(cid, width / 1000.0);
+                        }
+                    }
+                    i += 3;
+                }
+            }
+        }
+    }
+
+    // Découpe la chaîne en codes selon les plages de la CMap
+    fn next_code(&self, bytes: &[u8]) -> (u32, usize) {
+        for space in &self.code_spaces {
+            if bytes.len() >= space.length {
+                let code = code_value(&bytes[..space.length]);
+                if code >= space.low && code <= space.high {
+                    return (code, space.length);
+                }
+            }
+        }
+        let length = if self.composite { 2.min(bytes.len()) } else { 1 };
+        (code_value(&bytes[..length]), length)
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Vec<Glyph> {
+        let mut glyphs = Vec::new();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let (code, length) = self.next_code(&bytes[pos..]);
+            pos += length;
+            let text = match self.to_unicode.get(&code) {
+                Some(text) => text.clone(),
+                None => self.encoding.get(code as usize).cloned().flatten().unwrap_or_default(),
+            };
+            glyphs.push(Glyph {
+                code,
+                text,
+                width: self.widths.get(&code).copied().unwrap_or(self.default_width),
+                is_space: length == 1 && code == 32,
+            });
+        }
+        glyphs
+    }
+
+    // Les contours ne sont disponibles que pour les polices incorporées (FontFile2 / FontFile3)
+    pub fn has_outlines(&self) -> bool {
+        self.program.is_some()
+    }
+
+    // Contour du glyphe, en espace glyphe normalisé (1.0 = corps)
+    pub fn outline(&self, glyph: &Glyph) -> Option<Rc<Vec<SubPath>>> {
+        let program = self.program.as_ref()?;
+        if let Some(cached) = self.outlines.borrow().get(&glyph.code) {
+            return cached.clone();
+        }
+        let glyph_id = if self.composite {
+            program.cid_glyph(glyph.code, self.cid_to_gid.as_deref())
+        } else {
+            let unicode = glyph.text.chars().next();
+            program.simple_glyph(glyph.code, unicode, self.glyph_names.get(&glyph.code).map(String::as_str))
+        };
+        let outline = glyph_id.and_then(|id| program.outline(id)).map(Rc::new);
+        self.outlines.borrow_mut().insert(glyph.code, outline.clone());
+        outline
+    }
+}
+
+fn font_descriptor<'a>(doc: &'a Document, dict: &'a Dictionary) -> Option<&'a Dictionary> {
+    dict.get(b"FontDescriptor").ok().and_then(|o| resolve(doc, o).as_dict().ok())
+}
+
+// Encodage d'une police simple : /Encoding nommé, ou dictionnaire /BaseEncoding + /Differences
+fn simple_encoding(doc: &Document, dict: &Dictionary) -> (Vec<Option<String>>, HashMap<u32, String>) {
+    let encoding = dict.get(b"Encoding").ok().map(|o| resolve(doc, o));
+    let base_name = match encoding {
+        Some(Object::Name(name)) => String::from_utf8_lossy(name).to_string(),
+        Some(Object::Dictionary(d)) => d
+            .get(b"BaseEncoding")
+            .and_then(|o| o.as_name())
+            .map(|n| String::from_utf8_lossy(n).to_string())
+            .unwrap_or_else(|_| String::from("StandardEncoding")),
+        _ => String::from("StandardEncoding"),
+    };
+    let mut table = base_encoding(&base_name);
+    let mut glyph_names = HashMap::new();
+
+    if let Some(Object::Dictionary(d)) = encoding {
+        if let Ok(differences) = d.get(b"Differences").and_then(|o| resolve(doc, o).as_array()) {
+            let mut code = 0usize;
+            for item in differences {
+                match resolve(doc, item) {
+                    Object::Integer(start) => code = (*start).max(0) as usize,
+                    Object::Name(glyph_name) => {
+                        let glyph_name = String::from_utf8_lossy(glyph_name).to_string();
+                        if code < table.len() {
+                            table[code] = glyph_to_unicode(&glyph_name);
+                        }
+                        if let Ok(code) = u32::try_from(code) {
+                            glyph_names.insert(code, glyph_name);
+                        }
+                        code = code.saturating_add(1);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    (table, glyph_names)
+}
+
+// Moitié haute (0x80 à 0xFF) des encodages de base, 16 codes par ligne ; « \0 » marque un code sans glyphe
+const STANDARD_ENCODING: [&str; 8] = [
+    "\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+    "\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+    "\0¡¢£⁄¥ƒ§¤'“«‹›ﬁﬂ",
+    "\0–†‡·\0¶•‚„”»…‰\0¿",
+    "\0`´ˆ˜¯˘˙¨\0˚¸\0˝˛ˇ",
+    "—\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+    "\0Æ\0ª\0\0\0\0ŁØŒº\0\0\0\0",
+    "\0æ\0\0\0ı\0\0łøœß\0\0\0\0",
+];
+const WIN_ANSI_ENCODING: [&str; 8] = [
+    "€•‚ƒ„…†‡ˆ‰Š‹Œ•Ž•",
+    "•‘’“”•–—˜™š›œ•žŸ",
+    " ¡¢£¤¥¦§¨©ª«¬-®¯",
+    "°±²³´µ¶·¸¹º»¼½¾¿",
+    "ÀÁÂÃÄÅÆÇÈÉÊËÌÍÎÏ",
+    "ÐÑÒÓÔÕÖ×ØÙÚÛÜÝÞß",
+    "àáâãäåæçèéêëìíîï",
+    "ðñòóôõö÷øùúûüýþÿ",
+];
+const MAC_ROMAN_ENCODING: [&str; 8] = [
+    "ÄÅÇÉÑÖÜáàâäãåçéè",
+    "êëíìîïñóòôöõúùûü",
+    "†°¢£§•¶ß®©™´¨≠ÆØ",
+    "∞±≤≥¥µ∂∑∏π∫ªº\u{2126}æø", // 0xBD : signe ohm, distinct de l'oméga grec
+    "¿¡¬√ƒ≈∆«»… ÀÃÕŒœ",
+    "–—“”‘’÷◊ÿŸ⁄¤‹›ﬁﬂ",
+    "‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ",
+    "\u{f8ff}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ",
+];
+
+// Table des 256 codes d'un encodage de base nommé : ASCII imprimable en moitié basse, sauf les guillemets
+// de StandardEncoding et la puce 0x7F de WinAnsiEncoding ; un nom inconnu vaut StandardEncoding
+fn base_encoding(name: &str) -> Vec<Option<String>> {
+    let (high, low): (&[&str; 8], &[(u8, char)]) = match name {
+        "WinAnsiEncoding" => (&WIN_ANSI_ENCODING, &[(0x7F, '•')]),
+        "MacRomanEncoding" => (&MAC_ROMAN_ENCODING, &[]),
+        _ => (&STANDARD_ENCODING, &[(0x27, '’'), (0x60, '‘')]),
+    };
+    let mut table: Vec<Option<String>> = (0..0x80u8).map(|code| (0x20..0x7F).contains(&code).then(|| (code as char).to_string())).collect();
+    for &(code, c) in low {
+        table[code as usize] = Some(c.to_string());
+    }
+    table.extend(high.iter().flat_map(|row| row.chars()).map(|c| (c != '\0').then(|| c.to_string())));
+    table
+}
+
+// Noms de glyphes Adobe les plus courants hors ASCII ; les autres suivent les conventions uniXXXX / uXXXX
+const GLYPH_NAMES: &[(&str, char)] = &[
+    ("space", ' '), ("exclam", '!'), ("quotedbl", '"'), ("numbersign", '#'), ("dollar", '$'), ("percent", '%'),
+    ("ampersand", '&'), ("quotesingle", '\''), ("parenleft", '('), ("parenright", ')'), ("asterisk", '*'),
+    ("plus", '+'), ("comma", ','), ("hyphen", '-'), ("period", '.'), ("slash", '/'), ("zero", '0'), ("one", '1'),
+    ("two", '2'), ("three", '3'), ("four", '4'), ("five", '5'), ("six", '6'), ("seven", '7'), ("eight", '8'),
+    ("nine", '9'), ("colon", ':'), ("semicolon", ';'), ("less", '<'), ("equal", '='), ("greater", '>'),
+    ("question", '?'), ("at", '@'), ("bracketleft", '['), ("backslash", '\\'), ("bracketright", ']'),
+    ("asciicircum", '^'), ("underscore", '_'), ("grave", '`'), ("quoteleft", '‘'), ("quoteright", '’'),
+    ("braceleft", '{'), ("bar", '|'), ("braceright", '}'), ("asciitilde", '~'), ("exclamdown", '¡'),
+    ("cent", '¢'), ("sterling", '£'), ("yen", '¥'), ("section", '§'), ("dieresis", '¨'), ("copyright", '©'),
+    ("ordfeminine", 'ª'), ("guillemotleft", '«'), ("logicalnot", '¬'), ("registered", '®'), ("macron", '¯'),
+    ("degree", '°'), ("plusminus", '±'), ("twosuperior", '²'), ("threesuperior", '³'), ("acute", '´'),
+    ("mu", 'µ'), ("paragraph", '¶'), ("periodcentered", '·'), ("cedilla", '¸'), ("onesuperior", '¹'),
+    ("ordmasculine", 'º'), ("guillemotright", '»'), ("onequarter", '¼'), ("onehalf", '½'),
+    ("threequarters", '¾'), ("questiondown", '¿'), ("multiply", '×'), ("divide", '÷'), ("AE", 'Æ'),
+    ("ae", 'æ'), ("Oslash", 'Ø'), ("oslash", 'ø'), ("germandbls", 'ß'), ("OE", 'Œ'), ("oe", 'œ'),
+    ("Eth", 'Ð'), ("eth", 'ð'), ("Thorn", 'Þ'), ("thorn", 'þ'), ("dotlessi", 'ı'), ("endash", '–'),
+    ("emdash", '—'), ("quotedblleft", '“'), ("quotedblright", '”'), ("quotesinglbase", '‚'),
+    ("quotedblbase", '„'), ("dagger", '†'), ("daggerdbl", '‡'), ("bullet", '•'), ("ellipsis", '…'),
+    ("perthousand", '‰'), ("guilsinglleft", '‹'), ("guilsinglright", '›'), ("Euro", '€'), ("trademark", '™'),
+    ("minus", '−'), ("fraction", '⁄'), ("florin", 'ƒ'), ("circumflex", 'ˆ'), ("tilde", '˜'), ("ring", '˚'),
+    ("Omega", 'Ω'), ("Delta", 'Δ'), ("pi", 'π'), ("infinity", '∞'), ("lessequal", '≤'), ("greaterequal", '≥'),
+    ("notequal", '≠'), ("approxequal", '≈'), ("radical", '√'), ("summation", '∑'), ("integral", '∫'),
+    ("partialdiff", '∂'), ("nbspace", '\u{a0}'), ("sfthyphen", '\u{ad}'),
+];
+
+// Lettres accentuées : lettre de base suivie du nom de l'accent (eacute, Agrave...)
+const ACCENTS: &[(&str, &str, &str)] = &[
+    ("grave", "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),
+    ("acute", "AEIOUYaeiouy", "ÁÉÍÓÚÝáéíóúý"),
+    ("circumflex", "AEIOUaeiou", "ÂÊÎÔÛâêîôû"),
+    ("tilde", "ANOano", "ÃÑÕãñõ"),
+    ("dieresis", "AEIOUaeiouy", "ÄËÏÖÜäëïöüÿ"),
+    ("ring", "Aa", "Åå"),
+    ("cedilla", "Cc", "Çç"),
+    ("caron", "SZszCcEeRrNn", "ŠŽšžČčĚěŘřŇň"),
+];
+
+fn glyph_to_unicode(name: &str) -> Option<String> {
+    // Les suffixes (.sc, .alt, _xx) ne changent pas le caractère
+    let name = name.split('.').next().unwrap_or(name);
+    if let Some(&(_, c)) = GLYPH_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(c.to_string());
+    }
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphabetic() {
+            return Some(c.to_string());
+        }
+    }
+    for (accent, bases, results) in ACCENTS {
+        if let Some(base) = name.strip_suffix(accent) {
+            let mut chars = base.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                if let Some(result) = bases.chars().position(|b| b == c).and_then(|i| results.chars().nth(i)) {
+                    return Some(result.to_string());
+                }
+            }
+        }
+    }
+    if let Some(hex) = name.strip_prefix("uni") {
+        let units: Vec<u16> = hex
+            .as_bytes()
+            .chunks(4)
+            .filter_map(|c| std::str::from_utf8(c).ok().and_then(|s| u16::from_str_radix(s, 16).ok()))
+            .collect();
+        if !units.is_empty() {
+            return Some(String::from_utf16_lossy(&units));
+        }
+    }
+    if let Some(hex) = name.strip_prefix('u') {
+        if let Some(c) = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+            return Some(c.to_string());
+        }
+    }
+    None
+}
+
+// --- CMaps ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Hex(Vec<u8>),
+    ArrayStart,
+    ArrayEnd,
+    DictStart,
+    DictEnd,
+    Word(String),
+}
+
+fn tokenize(data: &[u8]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            // Dictionnaires `<< ... >>` (/CIDSystemInfo), à distinguer des chaînes hexadécimales
+            b'<' if data.get(i + 1) == Some(&b'<') => {
+                tokens.push(Token::DictStart);
+                i += 2;
+            }
+            b'>' if data.get(i + 1) == Some(&b'>') => {
+                tokens.push(Token::DictEnd);
+                i += 2;
+            }
+            b'<' => {
+                let end = data[i..].iter().position(|&b| b == b'>').map(|p| i + p).unwrap_or(data.len());
+                let digits: Vec<u8> = data[i + 1..end].iter().copied().filter(|b| b.is_ascii_hexdigit()).collect();
+                let bytes = digits
+                    .chunks(2)
+                    .map(|pair| {
+                        let hex = if pair.len() == 2 { [pair[0], pair[1]] } else { [pair[0], b'0'] };
+                        u8::from_str_radix(std::str::from_utf8(&hex).unwrap_or("00"), 16).unwrap_or(0)
+                    })
+                    .collect();
+                tokens.push(Token::Hex(bytes));
+                i = end + 1;
+            }
+            b'[' => {
+                tokens.push(Token::ArrayStart);
+                i += 1;
+            }
+            b']' => {
+                tokens.push(Token::ArrayEnd);
+                i += 1;
+            }
+            b'%' => {
+                while i < data.len() && data[i] != b'\n' && data[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            b if b.is_ascii_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                while i < data.len() && !data[i].is_ascii_whitespace() && !b"<>[]%".contains(&data[i]) {
+                    i += 1;
+                }
+                // Un délimiteur isolé (`>` seul) forme son propre mot : on avance toujours d'au moins un octet
+                if i == start {
+                    i += 1;
+                }
+                tokens.push(Token::Word(String::from_utf8_lossy(&data[start..i]).to_string()));
+            }
+        }
+    }
+    tokens
+}
+
+fn code_value(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32)
+}
+
+fn utf16_text(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks(2).map(|c| if c.len() == 2 { u16::from_be_bytes([c[0], c[1]]) } else { c[0] as u16 }).collect();
+    String::from_utf16_lossy(&units)
+}
+
+// Destination d'une plage `bfrange` : la dernière unité UTF-16 est incrémentée pour chaque code
+fn offset_text(bytes: &[u8], offset: u32) -> String {
+    let mut units: Vec<u16> = bytes.chunks(2).map(|c| if c.len() == 2 { u16::from_be_bytes([c[0], c[1]]) } else { c[0] as u16 }).collect();
+    if let Some(last) = units.last_mut() {
+        *last = last.wrapping_add(offset as u16);
+    }
+    String::from_utf16_lossy(&units)
+}
+
+// Sections utiles d'une CMap : plages de codes, `bfchar` et `bfrange`
+struct CMap {
+    code_spaces: Vec<CodeSpace>,
+    mappings: HashMap<u32, String>,
+}
+
+impl CMap {
+    fn parse(data: &[u8]) -> CMap {
+        let tokens = tokenize(data);
+        let mut cmap = CMap { code_spaces: Vec::new(), mappings: HashMap::new() };
+        let mut i = 0;
+        while i < tokens.len() {
+            match &tokens[i] {
+                Token::Word(w) if w == "begincodespacerange" => {
+                    i += 1;
+                    while let (Some(Token::Hex(low)), Some(Token::Hex(high))) = (tokens.get(i), tokens.get(i + 1)) {
+                        cmap.code_spaces.push(CodeSpace { length: low.len().max(1), low: code_value(low), high: code_value(high) });
+                        i += 2;
+                    }
+                }
+                Token::Word(w) if w == "beginbfchar" => {
+                    i += 1;
+                    while let (Some(Token::Hex(src)), Some(Token::Hex(dst))) = (tokens.get(i), tokens.get(i + 1)) {
+                        cmap.mappings.insert(code_value(src), utf16_text(dst));
+                        i += 2;
+                    }
+                }
+                Token::Word(w) if w == "beginbfrange" => {
+                    i += 1;
+                    while let (Some(Token::Hex(low)), Some(Token::Hex(high))) = (tokens.get(i), tokens.get(i + 1)) {
+                        let (low, high) = (code_value(low), code_value(high));
+                        match tokens.get(i + 2) {
+                            Some(Token::Hex(dst)) => {
+                                for code in low..=high.min(low.saturating_add(0xFFFF)) {
+                                    cmap.mappings.insert(code, offset_text(dst, code - low));
+                                }
+                                i += 3;
+                            }
+                            Some(Token::ArrayStart) => {
+                                // Forme `<bas> <haut> [<dst1> <dst2> ...]`
+                                i += 3;
+                                let mut code = Some(low);
+                                while let Some(Token::Hex(dst)) = tokens.get(i) {
+                                    // Destinations au-delà de u32::MAX ignorées
+                                    if let Some(code) = code {
+                                        cmap.mappings.insert(code, utf16_text(dst));
+                                    }
+                                    code = code.and_then(|code| code.checked_add(1));
+                                    i += 1;
+                                }
+                                if tokens.get(i) == Some(&Token::ArrayEnd) {
+                                    i += 1;
+                                }
+                            }
+                            _ => i += 2,
+                        }
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+        // Les plages les plus courtes sont essayées en premier, comme le fait la lecture octet par octet
+        cmap.code_spaces.sort_by_key(|space| space.length);
+        cmap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // CMap ToUnicode telle qu'écrite par les outils courants, avec son dictionnaire /CIDSystemInfo
+    const TO_UNICODE: &[u8] = b"/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def
+/CMapName /Adobe-Identity-UCS def
+/CMapType 2 def
+1 begincodespacerange
+<0000> <FFFF>
+endcodespacerange
+2 beginbfchar
+<0003> <0020>
+<0011> <0041>
+endbfchar
+2 beginbfrange
+<0044> <0046> <0061>
+<0050> <0051> [<00E9> <00660069>]
+endbfrange
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end
+";
+
+    #[test]
+    fn tokenize_dictionaries_and_stray_delimiters() {
+        let tokens = tokenize(b"<< /A <0102> >> > [<FF>]");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::DictStart,
+                Token::Word(String::from("/A")),
+                Token::Hex(vec![1, 2]),
+                Token::DictEnd,
+                Token::Word(String::from(">")),
+                Token::ArrayStart,
+                Token::Hex(vec![0xFF]),
+                Token::ArrayEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_to_unicode_cmap() {
+        let cmap = CMap::parse(TO_UNICODE);
+        assert_eq!(cmap.code_spaces.len(), 1);
+        assert_eq!((cmap.code_spaces[0].length, cmap.code_spaces[0].low, cmap.code_spaces[0].high), (2, 0, 0xFFFF));
+        assert_eq!(cmap.mappings.get(&0x03).map(String::as_str), Some(" "));
+        assert_eq!(cmap.mappings.get(&0x11).map(String::as_str), Some("A"));
+        assert_eq!(cmap.mappings.get(&0x44).map(String::as_str), Some("a"));
+        assert_eq!(cmap.mappings.get(&0x46).map(String::as_str), Some("c"));
+        assert_eq!(cmap.mappings.get(&0x50).map(String::as_str), Some("é"));
+        assert_eq!(cmap.mappings.get(&0x51).map(String::as_str), Some("fi"));
+        assert_eq!(cmap.mappings.len(), 7);
+    }
+
+    #[test]
+    fn base_encodings_match_lopdf() {
+        for name in ["StandardEncoding", "WinAnsiEncoding", "MacRomanEncoding"] {
+            let table = base_encoding(name);
+            assert_eq!(table.len(), 256);
+            for code in 0x20..=0xFFu8 {
+                let text = Document::decode_text(Some(name), &[code]);
+                assert_eq!(table[code as usize], (!text.is_empty()).then_some(text), "{} {:#04X}", name, code);
+            }
+        }
+        assert_eq!(base_encoding("WinAnsiEncoding")[0xE9].as_deref(), Some("é"));
+        assert_eq!(base_encoding("MacRomanEncoding")[0x8E].as_deref(), Some("é"));
+        assert_eq!(base_encoding("Inconnu")[0x27].as_deref(), Some("’"));
+    }
+
+    #[test]
+    fn parse_ranges_near_u32_max() {
+        let cmap = CMap::parse(b"1 beginbfrange <FFFFFFFE> <FFFFFFFF> [<0041> <0042> <0043>] endbfrange 1 beginbfrange <FFFFFFFF> <FFFFFFFF> <0044> endbfrange");
+        assert_eq!(cmap.mappings.get(&0xFFFF_FFFE).map(String::as_str), Some("A"));
+        assert_eq!(cmap.mappings.get(&0xFFFF_FFFF).map(String::as_str), Some("D"));
+        assert_eq!(cmap.mappings.len(), 2);
+    }
+}
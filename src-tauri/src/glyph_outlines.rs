@@ -0,0 +1,193 @@
+use crate::pdf_converter::{BezierEntity, LineEntity, PathSegment, Point, SubPath};
+use lopdf::{Dictionary, Document};
+use ttf_parser::{cff, GlyphId, OutlineBuilder};
+
+// Programme de police incorporé : /FontFile2 (TrueType) ou /FontFile3 (CFF nu ou OpenType)
+#[derive(Debug)]
+pub enum FontProgram {
+    TrueType(Vec<u8>),
+    Cff(Vec<u8>),
+}
+
+impl FontProgram {
+    // Programme du descripteur de police (de la police descendante pour une police composite)
+    pub fn load(doc: &Document, descriptor: &Dictionary) -> Option<FontProgram> {
+        let stream_of = |key: &[u8]| {
+            descriptor
+                .get(key)
+                .ok()
+                .and_then(|o| doc.dereference(o).ok())
+                .and_then(|(_, o)| o.as_stream().ok())
+        };
+        if let Some(stream) = stream_of(b"FontFile2") {
+            return Some(FontProgram::TrueType(stream.decompressed_content().unwrap_or_else(|_| stream.content.clone())));
+        }
+        let stream = stream_of(b"FontFile3")?;
+        let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+        match stream.dict.get(b"Subtype").and_then(|o| o.as_name()).unwrap_or(b"") {
+            b"OpenType" => Some(FontProgram::TrueType(data)),
+            b"Type1C" | b"CIDFontType0C" => Some(FontProgram::Cff(data)),
+            _ => None,
+        }
+    }
+
+    // Numéro de glyphe d'un code de police simple : table cmap (TrueType) ou encodage CFF, à défaut par nom
+    pub fn simple_glyph(&self, code: u32, unicode: Option<char>, name: Option<&str>) -> Option<u16> {
+        match self {
+            FontProgram::TrueType(data) => {
+                let face = ttf_parser::Face::parse(data, 0).ok()?;
+                if let Some(id) = name.and_then(|n| face.glyph_index_by_name(n)) {
+                    return Some(id.0);
+                }
+                if let Some(id) = unicode.and_then(|c| face.glyph_index(c)) {
+                    return Some(id.0);
+                }
+                // Polices symboliques : cmap (3,0) décalée en 0xF000, ou cmap Macintosh (1,0) sur le code brut
+                let cmap = face.tables().cmap?;
+                cmap.subtables
+                    .into_iter()
+                    .find_map(|subtable| match (subtable.platform_id, subtable.encoding_id) {
+                        (ttf_parser::PlatformId::Windows, 0) => subtable.glyph_index(0xF000 + code).or_else(|| subtable.glyph_index(code)),
+                        (ttf_parser::PlatformId::Macintosh, 0) => subtable.glyph_index(code),
+                        _ => None,
+                    })
+                    .map(|id| id.0)
+            }
+            FontProgram::Cff(data) => {
+                let table = cff::Table::parse(data)?;
+                name.and_then(|n| table.glyph_index_by_name(n)).or_else(|| table.glyph_index(code as u8)).map(|id| id.0)
+            }
+        }
+    }
+
+    // Numéro de glyphe d'un CID : /CIDToGIDMap pour TrueType, jeu de caractères pour CFF
+    pub fn cid_glyph(&self, cid: u32, cid_to_gid: Option<&[u16]>) -> Option<u16> {
+        match self {
+            FontProgram::TrueType(_) => match cid_to_gid {
+                Some(map) => map.get(cid as usize).copied(),
+                None => u16::try_from(cid).ok(),
+            },
+            FontProgram::Cff(data) => {
+                let table = cff::Table::parse(data)?;
+                match table.glyph_cid(GlyphId(0)) {
+                    // Police CFF à CID : recherche du glyphe portant ce CID
+                    Some(_) => (0..table.number_of_glyphs()).find(|&gid| table.glyph_cid(GlyphId(gid)) == Some(cid as u16)),
+                    None => u16::try_from(cid).ok(),
+                }
+            }
+        }
+    }
+
+    // Contours du glyphe en espace glyphe normalisé (1.0 = corps de la police)
+    pub fn outline(&self, glyph: u16) -> Option<Vec<SubPath>> {
+        match self {
+            FontProgram::TrueType(data) => {
+                let face = ttf_parser::Face::parse(data, 0).ok()?;
+                let scale = 1.0 / face.units_per_em() as f64;
+                let mut builder = SubPathBuilder::new([scale, 0.0, 0.0, scale, 0.0, 0.0]);
+                face.outline_glyph(GlyphId(glyph), &mut builder)?;
+                Some(builder.finish())
+            }
+            FontProgram::Cff(data) => {
+                let table = cff::Table::parse(data)?;
+                let m = table.matrix();
+                let matrix = [m.sx, m.ky, m.kx, m.sy, m.tx, m.ty].map(|v| v as f64);
+                let mut builder = SubPathBuilder::new(matrix);
+                table.outline(GlyphId(glyph), &mut builder).ok()?;
+                Some(builder.finish())
+            }
+        }
+    }
+}
+
+// /CIDToGIDMap sous forme de flux (deux octets par CID) ; `None` pour /Identity
+pub fn cid_to_gid_map(doc: &Document, descendant: &Dictionary) -> Option<Vec<u16>> {
+    let stream = descendant
+        .get(b"CIDToGIDMap")
+        .ok()
+        .and_then(|o| doc.dereference(o).ok())
+        .and_then(|(_, o)| o.as_stream().ok())?;
+    let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+    Some(data.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect())
+}
+
+// Reconstruit les contours en sous-chemins fermés (les quadratiques TrueType deviennent des Béziers cubiques)
+struct SubPathBuilder {
+    matrix: [f64; 6],
+    subpaths: Vec<SubPath>,
+    current: SubPath,
+    last: Point,
+}
+
+impl SubPathBuilder {
+    fn new(matrix: [f64; 6]) -> Self {
+        SubPathBuilder { matrix, subpaths: Vec::new(), current: SubPath::default(), last: Point { x: 0.0, y: 0.0 } }
+    }
+
+    fn point(&self, x: f32, y: f32) -> Point {
+        let (x, y) = (x as f64, y as f64);
+        let m = &self.matrix;
+        Point { x: m[0] * x + m[2] * y + m[4], y: m[1] * x + m[3] * y + m[5] }
+    }
+
+    fn finish(mut self) -> Vec<SubPath> {
+        self.close();
+        self.subpaths
+    }
+}
+
+impl OutlineBuilder for SubPathBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.close();
+        self.last = self.point(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let end = self.point(x, y);
+        self.current.segments.push(PathSegment::Line(LineEntity { start: self.last, end }));
+        self.last = end;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (q, end) = (self.point(x1, y1), self.point(x, y));
+        let p0 = self.last;
+        let p1 = Point { x: p0.x + 2.0 / 3.0 * (q.x - p0.x), y: p0.y + 2.0 / 3.0 * (q.y - p0.y) };
+        let p2 = Point { x: end.x + 2.0 / 3.0 * (q.x - end.x), y: end.y + 2.0 / 3.0 * (q.y - end.y) };
+        self.current.segments.push(PathSegment::Bezier(BezierEntity { p0, p1, p2, p3: end }));
+        self.last = end;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (p1, p2, p3) = (self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+        self.current.segments.push(PathSegment::Bezier(BezierEntity { p0: self.last, p1, p2, p3 }));
+        self.last = p3;
+    }
+
+    fn close(&mut self) {
+        if !self.current.segments.is_empty() {
+            let mut subpath = std::mem::take(&mut self.current);
+            subpath.close();
+            self.subpaths.push(subpath);
+        }
+    }
+}
+
+// Applique une transformation à un contour de glyphe (matrice de rendu du texte)
+pub fn transform_subpaths(subpaths: &[SubPath], apply: impl Fn(Point) -> Point) -> Vec<SubPath> {
+    subpaths
+        .iter()
+        .map(|subpath| SubPath {
+            segments: subpath
+                .segments
+                .iter()
+                .map(|segment| match segment {
+                    PathSegment::Line(line) => PathSegment::Line(LineEntity { start: apply(line.start), end: apply(line.end) }),
+                    PathSegment::Bezier(b) => PathSegment::Bezier(BezierEntity { p0: apply(b.p0), p1: apply(b.p1), p2: apply(b.p2), p3: apply(b.p3) }),
+                    // Les contours de glyphes ne contiennent que des droites et des Béziers
+                    other => other.clone(),
+                })
+                .collect(),
+            closed: subpath.closed,
+        })
+        .collect()
+}
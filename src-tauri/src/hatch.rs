@@ -0,0 +1,174 @@
+use crate::pdf_converter::{FillRule, Point};
+use dxf::CodePair;
+
+// Contour fermé d'une hachure, en coordonnées du dessin : sommets et renflement (bulge) du segment qui en part
+pub struct HatchLoop {
+    pub vertices: Vec<(Point, f64)>,
+}
+
+impl HatchLoop {
+    // Polygone approché : le milieu de chaque arc est ajouté pour que les cercles (deux demi-arcs) aient une aire
+    fn polygon(&self) -> Vec<Point> {
+        let mut polygon = Vec::new();
+        for (i, &(a, bulge)) in self.vertices.iter().enumerate() {
+            polygon.push(a);
+            if bulge != 0.0 {
+                let b = self.vertices[(i + 1) % self.vertices.len()].0;
+                let (dx, dy) = (b.x - a.x, b.y - a.y);
+                polygon.push(Point { x: (a.x + b.x) / 2.0 + dy * bulge / 2.0, y: (a.y + b.y) / 2.0 - dx * bulge / 2.0 });
+            }
+        }
+        polygon
+    }
+}
+
+fn signed_area(polygon: &[Point]) -> f64 {
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum::<f64>()
+        / 2.0
+}
+
+fn winding_number(polygon: &[Point], p: Point) -> i32 {
+    let mut winding = 0;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (a.y <= p.y) != (b.y <= p.y) {
+            let x = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if x > p.x {
+                winding += if b.y > a.y { 1 } else { -1 };
+            }
+        }
+    }
+    winding
+}
+
+// Contours retenus pour une hachure de style « Normal » (îlots alternés, donc pair-impair).
+// En non nul, un contour n'est gardé que s'il sépare une zone remplie d'une zone vide :
+// un contour intérieur de même sens que celui qui l'entoure ne crée pas de trou.
+pub fn boundary_loops(loops: Vec<HatchLoop>, rule: FillRule) -> Vec<HatchLoop> {
+    let polygons: Vec<Vec<Point>> = loops.iter().map(HatchLoop::polygon).collect();
+    let areas: Vec<f64> = polygons.iter().map(|p| signed_area(p)).collect();
+    loops
+        .into_iter()
+        .enumerate()
+        .filter(|&(i, _)| areas[i].abs() > 1e-9)
+        .filter(|&(i, _)| match rule {
+            FillRule::EvenOdd => true,
+            FillRule::NonZero => {
+                let p = polygons[i][0];
+                let outside: i32 = polygons
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i && areas[j].abs() > 1e-9)
+                    .map(|(_, polygon)| winding_number(polygon, p))
+                    .sum();
+                let inside = outside + if areas[i] > 0.0 { 1 } else { -1 };
+                (outside == 0) != (inside == 0)
+            }
+        })
+        .map(|(_, hatch_loop)| hatch_loop)
+        .collect()
+}
+
+// Groupes propres à l'entité HATCH (sous-classe AcDbHatch) : remplissage SOLID, contours en polylignes
+pub fn hatch_pairs(loops: &[HatchLoop]) -> Vec<CodePair> {
+    let mut pairs = vec![
+        CodePair::new_str(100, "AcDbHatch"),
+        CodePair::new_f64(10, 0.0),
+        CodePair::new_f64(20, 0.0),
+        CodePair::new_f64(30, 0.0),
+        CodePair::new_f64(210, 0.0),
+        CodePair::new_f64(220, 0.0),
+        CodePair::new_f64(230, 1.0),
+        CodePair::new_str(2, "SOLID"),
+        CodePair::new_i16(70, 1),
+        CodePair::new_i16(71, 0),
+        CodePair::new_i32(91, loops.len() as i32),
+    ];
+    for hatch_loop in loops {
+        let has_bulge = hatch_loop.vertices.iter().any(|&(_, bulge)| bulge != 0.0);
+        pairs.push(CodePair::new_i32(92, 2)); // contour de type polyligne
+        pairs.push(CodePair::new_i16(72, has_bulge as i16));
+        pairs.push(CodePair::new_i16(73, 1));
+        pairs.push(CodePair::new_i32(93, hatch_loop.vertices.len() as i32));
+        for &(p, bulge) in &hatch_loop.vertices {
+            pairs.push(CodePair::new_f64(10, p.x));
+            pairs.push(CodePair::new_f64(20, p.y));
+            if has_bulge {
+                pairs.push(CodePair::new_f64(42, bulge));
+            }
+        }
+        pairs.push(CodePair::new_i32(97, 0));
+    }
+    pairs.push(CodePair::new_i16(75, 0)); // style Normal : îlots alternés
+    pairs.push(CodePair::new_i16(76, 1)); // motif prédéfini
+    pairs.push(CodePair::new_i32(98, 0));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Carré de côté `size` centré en (5, 5), parcouru dans le sens trigonométrique ou horaire
+    fn square(size: f64, counter_clockwise: bool) -> HatchLoop {
+        let (lo, hi) = (5.0 - size / 2.0, 5.0 + size / 2.0);
+        let mut corners = vec![(lo, lo), (hi, lo), (hi, hi), (lo, hi)];
+        if !counter_clockwise {
+            corners.reverse();
+        }
+        HatchLoop { vertices: corners.into_iter().map(|(x, y)| (Point { x, y }, 0.0)).collect() }
+    }
+
+    fn sizes(loops: &[HatchLoop]) -> Vec<f64> {
+        loops.iter().map(|l| l.vertices.iter().map(|(p, _)| p.x).fold(f64::MIN, f64::max) - 5.0).collect()
+    }
+
+    #[test]
+    fn boundary_loops_follow_the_fill_rule() {
+        // Trou de même sens : ignoré en non nul, conservé en pair-impair
+        let same = || vec![square(10.0, true), square(4.0, true)];
+        assert_eq!(sizes(&boundary_loops(same(), FillRule::NonZero)), vec![5.0]);
+        assert_eq!(sizes(&boundary_loops(same(), FillRule::EvenOdd)), vec![5.0, 2.0]);
+        // Trou de sens opposé : conservé dans les deux cas
+        let opposite = vec![square(10.0, true), square(4.0, false)];
+        assert_eq!(sizes(&boundary_loops(opposite, FillRule::NonZero)), vec![5.0, 2.0]);
+        // Contour dégénéré, sans aire
+        assert!(boundary_loops(vec![square(0.0, true)], FillRule::EvenOdd).is_empty());
+    }
+
+    #[test]
+    fn placeholder_point_becomes_a_hatch() {
+        let mut drawing = dxf::Drawing::new();
+        drawing.header.version = dxf::enums::AcadVersion::R2000;
+        let mut point = dxf::entities::Entity::new(dxf::entities::EntityType::ModelPoint(Default::default()));
+        point.common.layer = String::from("Murs");
+        let handle = drawing.add_entity(point).common.handle;
+        let mut buffer = Vec::new();
+        drawing.save(&mut buffer).unwrap();
+
+        let hatches = std::collections::HashMap::from([(handle.0, hatch_pairs(&[square(10.0, true)]))]);
+        let patched = crate::dxf_patch::Patches { hatches, ..Default::default() }.apply(&String::from_utf8_lossy(&buffer));
+        let lines: Vec<&str> = patched.split("\r\n").collect();
+        let start = lines.iter().position(|&line| line == "HATCH").unwrap();
+        let pairs: Vec<(&str, &str)> = lines[start + 1..]
+            .chunks(2)
+            .map(|pair| (pair[0].trim(), pair[1].trim()))
+            .take_while(|&(code, _)| code != "0")
+            .collect();
+
+        // Le handle et le calque du POINT sont conservés, sa géométrie est remplacée par celle de la hachure
+        let value = |code: &str| pairs.iter().find(|(c, _)| *c == code).map(|(_, v)| *v);
+        assert_eq!(value("5"), Some(format!("{:X}", handle.0).as_str()));
+        assert_eq!(value("8"), Some("Murs"));
+        assert_eq!(value("2"), Some("SOLID"));
+        assert_eq!(value("91"), Some("1"));
+        assert_eq!(value("93"), Some("4"));
+        assert!(pairs.contains(&("100", "AcDbHatch")));
+        assert!(!pairs.contains(&("100", "AcDbPoint")));
+        assert!(!patched.contains("\r\nPOINT\r\n"));
+        assert_eq!(pairs.iter().filter(|(code, _)| *code == "10").count(), 5);
+    }
+}
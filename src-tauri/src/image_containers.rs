@@ -0,0 +1,236 @@
+use lopdf::Dictionary;
+
+// Flux JPXDecode : fichier JP2 complet (boîte de signature) ou codestream J2K nu
+pub fn jpx_extension(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x00\x00\x00\x0cjP  \r\n\x87\n") {
+        "jp2"
+    } else {
+        "j2k"
+    }
+}
+
+fn param_i64(params: Option<&Dictionary>, key: &[u8], default: i64) -> i64 {
+    params.and_then(|p| p.get(key).and_then(|o| o.as_i64()).ok()).unwrap_or(default)
+}
+
+fn param_bool(params: Option<&Dictionary>, key: &[u8]) -> bool {
+    params.and_then(|p| p.get(key).and_then(|o| o.as_bool()).ok()).unwrap_or(false)
+}
+
+// Données CCITT G3/G4 encapsulées dans un TIFF d'une seule bande.
+// `inverted` : /Decode [1 0] sur l'image, qui inverse le rendu au même titre que /BlackIs1.
+pub fn ccitt_tiff(data: &[u8], params: Option<&Dictionary>, width: u32, height: u32, inverted: bool) -> Vec<u8> {
+    let k = param_i64(params, b"K", 0);
+    let columns = param_i64(params, b"Columns", width as i64).max(1) as u32;
+    let rows = match param_i64(params, b"Rows", 0) {
+        rows if rows > 0 => rows as u32,
+        _ => height,
+    };
+    // Les codes CCITT décrivent des pixels noirs : en TIFF WhiteIsZero (0), le noir est décodé à 1
+    let photometric = if param_bool(params, b"BlackIs1") != inverted { 1 } else { 0 };
+
+    let mut entries: Vec<(u16, u16, u32)> = vec![
+        (256, 4, columns),                   // ImageWidth
+        (257, 4, rows),                      // ImageLength
+        (258, 3, 1),                         // BitsPerSample
+        (259, 3, if k < 0 { 4 } else { 3 }), // Compression : 4 = Group 4, 3 = Group 3
+        (262, 3, photometric),               // PhotometricInterpretation
+        (273, 4, 0),                         // StripOffsets, renseigné plus bas
+        (277, 3, 1),                         // SamplesPerPixel
+        (278, 4, rows),                      // RowsPerStrip
+        (279, 4, data.len() as u32),         // StripByteCounts
+    ];
+    if k >= 0 {
+        // T4Options : bit 0 = codage 2D (K > 0), bit 2 = lignes alignées sur l'octet
+        let fill_bits = if param_bool(params, b"EncodedByteAlign") { 4 } else { 0 };
+        entries.push((292, 4, if k > 0 { 1 } else { 0 } | fill_bits));
+    } else {
+        entries.push((293, 4, 0)); // T6Options
+    }
+
+    let ifd_size = 2 + entries.len() * 12 + 4;
+    let data_offset = (8 + ifd_size) as u32;
+    let mut tiff = Vec::with_capacity(data_offset as usize + data.len());
+    tiff.extend_from_slice(b"II*\x00");
+    tiff.extend_from_slice(&8u32.to_le_bytes());
+    tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, field_type, value) in entries {
+        let value = if tag == 273 { data_offset } else { value };
+        tiff.extend_from_slice(&tag.to_le_bytes());
+        tiff.extend_from_slice(&field_type.to_le_bytes());
+        tiff.extend_from_slice(&1u32.to_le_bytes());
+        // Les valeurs SHORT sont cadrées à gauche dans le champ de 4 octets
+        match field_type {
+            3 => {
+                tiff.extend_from_slice(&(value as u16).to_le_bytes());
+                tiff.extend_from_slice(&[0, 0]);
+            }
+            _ => tiff.extend_from_slice(&value.to_le_bytes()),
+        }
+    }
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    tiff.extend_from_slice(data);
+    tiff
+}
+
+// Numéro du dernier segment d'un flux JBIG2 (organisation séquentielle), `None` si l'en-tête est illisible
+fn last_segment_number(data: &[u8]) -> Option<u32> {
+    let read_u32 = |at: usize| data.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let mut offset = 0;
+    let mut last = None;
+    while offset < data.len() {
+        let number = read_u32(offset)?;
+        let flags = *data.get(offset + 4)?;
+        let mut at = offset + 5;
+        // Segments référencés : compte court (3 bits) ou long (29 bits) suivi des bits de rétention
+        let count_byte = *data.get(at)?;
+        let referred = if count_byte >> 5 == 7 {
+            let count = read_u32(at)? & 0x1FFF_FFFF;
+            at += 4 + (count as usize + 1).div_ceil(8);
+            count
+        } else {
+            at += 1;
+            (count_byte >> 5) as u32
+        };
+        let reference_size = match number {
+            0..=256 => 1,
+            257..=65536 => 2,
+            _ => 4,
+        };
+        at += referred as usize * reference_size;
+        at += if flags & 0x40 != 0 { 4 } else { 1 };
+        let length = read_u32(at)?;
+        // Longueur inconnue (région générique immédiate) : impossible de poursuivre
+        if length == u32::MAX {
+            return None;
+        }
+        offset = at + 4 + length as usize;
+        last = Some(number);
+    }
+    last
+}
+
+fn jbig2_segment(number: u32, segment_type: u8, page: u8) -> [u8; 11] {
+    let mut segment = [0u8; 11];
+    segment[..4].copy_from_slice(&number.to_be_bytes());
+    segment[4] = segment_type;
+    segment[6] = page;
+    segment
+}
+
+// Fichier JBIG2 autonome : en-tête, segments globaux (/JBIG2Globals), segments de la page,
+// puis les segments de fin de page et de fin de fichier que le PDF omet
+pub fn jbig2_file(globals: Option<&[u8]>, data: &[u8]) -> Vec<u8> {
+    let mut file = Vec::with_capacity(globals.map_or(0, <[u8]>::len) + data.len() + 35);
+    file.extend_from_slice(b"\x97JB2\r\n\x1a\n");
+    file.push(0x01); // organisation séquentielle, nombre de pages connu
+    file.extend_from_slice(&1u32.to_be_bytes());
+    if let Some(globals) = globals {
+        file.extend_from_slice(globals);
+    }
+    file.extend_from_slice(data);
+    let last = last_segment_number(data).map(|last| globals.and_then(last_segment_number).map_or(last, |g| g.max(last)));
+    if let Some(last) = last {
+        file.extend_from_slice(&jbig2_segment(last + 1, 49, 1)); // fin de page
+        file.extend_from_slice(&jbig2_segment(last + 2, 51, 0)); // fin de fichier
+    }
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    // Entrées de l'IFD : (étiquette, type, valeur brute sur 4 octets)
+    fn ifd_entries(tiff: &[u8]) -> Vec<(u16, u16, [u8; 4])> {
+        let offset = u32::from_le_bytes(tiff[4..8].try_into().unwrap()) as usize;
+        let count = u16::from_le_bytes([tiff[offset], tiff[offset + 1]]) as usize;
+        (0..count)
+            .map(|i| {
+                let entry = &tiff[offset + 2 + 12 * i..offset + 14 + 12 * i];
+                assert_eq!(u32::from_le_bytes(entry[4..8].try_into().unwrap()), 1);
+                (u16::from_le_bytes([entry[0], entry[1]]), u16::from_le_bytes([entry[2], entry[3]]), entry[8..12].try_into().unwrap())
+            })
+            .collect()
+    }
+
+    fn value(entries: &[(u16, u16, [u8; 4])], tag: u16) -> Option<u32> {
+        entries.iter().find(|e| e.0 == tag).map(|e| u32::from_le_bytes(e.2))
+    }
+
+    #[test]
+    fn ccitt_group_4_tiff_layout() {
+        let data = [0x26, 0xA0, 0x00, 0x10];
+        let params = dictionary! {"K" => -1, "Columns" => 1728};
+        let tiff = ccitt_tiff(&data, Some(&params), 0, 40, false);
+        assert_eq!(&tiff[..8], b"II*\x00\x08\x00\x00\x00");
+
+        let entries = ifd_entries(&tiff);
+        let tags: Vec<u16> = entries.iter().map(|e| e.0).collect();
+        assert_eq!(tags, vec![256, 257, 258, 259, 262, 273, 277, 278, 279, 293]);
+        assert!(tags.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(value(&entries, 256), Some(1728));
+        assert_eq!(value(&entries, 257), Some(40));
+        // Valeur SHORT cadrée à gauche : Compression = 4 (Group 4)
+        let compression = entries.iter().find(|e| e.0 == 259).unwrap();
+        assert_eq!((compression.1, compression.2), (3, [4, 0, 0, 0]));
+        assert_eq!(value(&entries, 262), Some(0));
+        // Les données suivent l'IFD, à l'adresse indiquée par StripOffsets
+        let strip = value(&entries, 273).unwrap() as usize;
+        assert_eq!(strip, 8 + 2 + 10 * 12 + 4);
+        assert_eq!(&tiff[strip..], &data);
+        assert_eq!(value(&entries, 279), Some(data.len() as u32));
+    }
+
+    #[test]
+    fn ccitt_group_3_options_and_polarity() {
+        let g3 = |params: lopdf::Dictionary, inverted: bool| ifd_entries(&ccitt_tiff(&[0], Some(&params), 100, 10, inverted));
+        let one_d = g3(dictionary! {}, false);
+        assert_eq!(value(&one_d, 259), Some(3));
+        assert_eq!(value(&one_d, 292), Some(0));
+        assert_eq!(value(&one_d, 293), None);
+
+        let two_d = g3(dictionary! {"K" => 2, "EncodedByteAlign" => true, "Rows" => 7}, false);
+        assert_eq!(value(&two_d, 292), Some(5));
+        assert_eq!(value(&two_d, 257), Some(7));
+        assert_eq!(value(&two_d, 278), Some(7));
+
+        // /BlackIs1 et /Decode [1 0] inversent chacun la photométrie
+        assert_eq!(value(&g3(dictionary! {"BlackIs1" => true}, false), 262), Some(1));
+        assert_eq!(value(&g3(dictionary! {}, true), 262), Some(1));
+        assert_eq!(value(&g3(dictionary! {"BlackIs1" => true}, true), 262), Some(0));
+    }
+
+    // Segment JBIG2 à en-tête court : numéro, type, aucun segment référencé, page 1
+    fn segment(number: u32, segment_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut segment = number.to_be_bytes().to_vec();
+        segment.extend_from_slice(&[segment_type, 0x00, 0x01]);
+        segment.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        segment.extend_from_slice(data);
+        segment
+    }
+
+    #[test]
+    fn jbig2_segments_are_numbered_after_the_last_one() {
+        let mut page = segment(1, 48, &[0; 19]);
+        page.extend(segment(2, 38, &[0xAA, 0xBB]));
+        assert_eq!(last_segment_number(&page), Some(2));
+        assert_eq!(last_segment_number(&segment(0, 0, &[1, 2, 3])), Some(0));
+        // En-tête tronqué : la longueur des données manque
+        assert_eq!(last_segment_number(&page[..8]), None);
+        assert_eq!(last_segment_number(&[0, 0, 0]), None);
+
+        let globals = segment(5, 0, &[0x01]);
+        let file = jbig2_file(Some(&globals), &page);
+        assert!(file.starts_with(b"\x97JB2\r\n\x1a\n\x01\x00\x00\x00\x01"));
+        // Fin de page puis fin de fichier, numérotées après le plus grand numéro
+        let tail = &file[file.len() - 22..];
+        assert_eq!(&tail[..5], &[0, 0, 0, 6, 49]);
+        assert_eq!(&tail[11..16], &[0, 0, 0, 7, 51]);
+        assert_eq!(&file[13..13 + globals.len()], globals.as_slice());
+
+        // Sans numéro lisible, aucun segment de fin n'est ajouté
+        assert_eq!(jbig2_file(None, &[0, 0]).len(), 13 + 2);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::color::ColorSpace;
+use crate::image_containers;
+use crate::pdf_objects::{as_f64, resolve};
+use image::{DynamicImage, GrayAlphaImage, GrayImage, RgbImage, RgbaImage};
+use lopdf::{Dictionary, Document, Object, Stream};
+use zune_core::bytestream::ZCursor;
+use zune_core::colorspace::ColorSpace as JpegColorSpace;
+use zune_core::options::DecoderOptions;
+
+// Image décodée, prête à être écrite à côté du DXF
+pub enum DecodedImage {
+    // Flux recopié dans un format de fichier image (JPEG gris ou RGB, JPEG 2000, TIFF CCITT, JBIG2)
+    Encoded { data: Vec<u8>, extension: &'static str },
+    Raster(DynamicImage),
+}
+
+fn filter_names(doc: &Document, dict: &Dictionary) -> Vec<Vec<u8>> {
+    match dict.get(b"Filter").map(|o| resolve(doc, o)) {
+        Ok(Object::Name(name)) => vec![name.clone()],
+        Ok(Object::Array(array)) => array.iter().filter_map(|o| resolve(doc, o).as_name().ok().map(<[u8]>::to_vec)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Paramètres (/DecodeParms) du filtre de rang `index` : dictionnaire unique ou tableau aligné sur /Filter
+fn filter_params<'a>(doc: &'a Document, dict: &'a Dictionary, index: usize) -> Option<&'a Dictionary> {
+    match resolve(doc, dict.get(b"DecodeParms").ok()?) {
+        Object::Dictionary(params) => Some(params),
+        Object::Array(array) => resolve(doc, array.get(index)?).as_dict().ok(),
+        _ => None,
+    }
+}
+
+// Applique les filtres génériques (Flate, LZW...) précédant un éventuel filtre d'image.
+// `decompressed_content` refuse les flux /Subtype /Image : le décodage se fait sur une copie sans sous-type.
+fn decode_filters(stream: &Stream, filters: &[Vec<u8>]) -> Option<Vec<u8>> {
+    if filters.is_empty() {
+        return Some(stream.content.clone());
+    }
+    let mut dict = stream.dict.clone();
+    dict.remove(b"Subtype");
+    dict.set("Filter", Object::Array(filters.iter().map(|name| Object::Name(name.clone())).collect()));
+    Stream::new(dict, stream.content.clone()).decompressed_content().ok()
+}
+
+// Échantillons bruts d'une image : composantes par pixel et bits par composante, lignes alignées sur l'octet
+struct Samples {
+    data: Vec<u8>,
+    width: usize,
+    height: usize,
+    components: usize,
+    bits: usize,
+}
+
+impl Samples {
+    fn row_bytes(&self) -> usize {
+        (self.width * self.components * self.bits).div_ceil(8)
+    }
+
+    fn is_complete(&self) -> bool {
+        self.width > 0 && self.height > 0 && self.components > 0 && self.data.len() >= self.row_bytes() * self.height
+    }
+
+    fn max_value(&self) -> u32 {
+        (1u32 << self.bits) - 1
+    }
+
+    // Valeur brute de la composante `c` du pixel (x, y)
+    fn get(&self, x: usize, y: usize, c: usize) -> u32 {
+        let row = &self.data[y * self.row_bytes()..];
+        let index = x * self.components + c;
+        match self.bits {
+            8 => row[index] as u32,
+            16 => u16::from_be_bytes([row[2 * index], row[2 * index + 1]]) as u32,
+            bits => {
+                let bit = index * bits;
+                let byte = row[bit / 8] as u32;
+                (byte >> (8 - bits - bit % 8)) & ((1 << bits) - 1)
+            }
+        }
+    }
+}
+
+// Tableau /Decode : bornes [min, max] de chaque composante, par défaut [0, 1] (ou [0, 2^bpc - 1] en Indexed)
+fn decode_ranges(doc: &Document, dict: &Dictionary, components: usize, default_max: f64) -> Vec<(f64, f64)> {
+    let array = dict.get(b"Decode").ok().and_then(|o| resolve(doc, o).as_array().ok());
+    (0..components)
+        .map(|c| match array {
+            Some(array) if array.len() >= 2 * (c + 1) => (as_f64(resolve(doc, &array[2 * c])), as_f64(resolve(doc, &array[2 * c + 1]))),
+            _ => (0.0, default_max),
+        })
+        .collect()
+}
+
+fn has_default_decode(doc: &Document, dict: &Dictionary, components: usize) -> bool {
+    decode_ranges(doc, dict, components, 1.0).iter().all(|&(min, max)| min == 0.0 && max == 1.0)
+}
+
+fn dimension(doc: &Document, dict: &Dictionary, key: &[u8]) -> usize {
+    dict.get(key).map(|o| as_f64(resolve(doc, o))).unwrap_or(0.0).max(0.0) as usize
+}
+
+// Marqueur APP14 « Adobe » : les JPEG CMYK d'Adobe stockent des valeurs inversées
+fn has_adobe_marker(jpeg: &[u8]) -> bool {
+    jpeg.windows(11).take(65536).any(|w| w[0] == 0xFF && w[1] == 0xEE && &w[4..9] == b"Adobe")
+}
+
+// Décode un flux DCT en échantillons 8 bits : CMYK bruts (remis en encrage si Adobe), sinon gris ou RGB
+fn decode_jpeg(jpeg: &[u8]) -> Option<Samples> {
+    let options = DecoderOptions::default().set_strict_mode(false).set_max_width(usize::MAX).set_max_height(usize::MAX);
+    let mut decoder = zune_jpeg::JpegDecoder::new_with_options(ZCursor::new(jpeg), options);
+    decoder.decode_headers().ok()?;
+    let (width, height) = decoder.dimensions()?;
+    let output = match decoder.input_colorspace()? {
+        JpegColorSpace::CMYK => JpegColorSpace::CMYK,
+        JpegColorSpace::Luma => JpegColorSpace::Luma,
+        // YCCK (toujours Adobe) et YCbCr sont convertis par le décodeur
+        _ => JpegColorSpace::RGB,
+    };
+    decoder.set_options(options.jpeg_set_out_colorspace(output));
+    let mut data = decoder.decode().ok()?;
+    if output == JpegColorSpace::CMYK && has_adobe_marker(jpeg) {
+        data.iter_mut().for_each(|v| *v = 255 - *v);
+    }
+    Some(Samples { data, width, height, components: output.num_components(), bits: 8 })
+}
+
+// Échantillons d'une image non JPEG, après les filtres génériques
+fn raw_samples(doc: &Document, stream: &Stream, components: usize) -> Option<Samples> {
+    let filters = filter_names(doc, &stream.dict);
+    if filters.iter().any(|f| matches!(f.as_slice(), b"DCTDecode" | b"DCT")) {
+        let generic: Vec<Vec<u8>> = filters.iter().take_while(|f| !matches!(f.as_slice(), b"DCTDecode" | b"DCT")).cloned().collect();
+        return decode_jpeg(&decode_filters(stream, &generic)?);
+    }
+    if filters.iter().any(|f| matches!(f.as_slice(), b"JPXDecode" | b"CCITTFaxDecode" | b"CCF" | b"JBIG2Decode")) {
+        return None;
+    }
+    let dict = &stream.dict;
+    let bits = if dict.get(b"ImageMask").and_then(|o| o.as_bool()).unwrap_or(false) {
+        1
+    } else {
+        dimension(doc, dict, b"BitsPerComponent").max(1)
+    };
+    if !matches!(bits, 1 | 2 | 4 | 8 | 16) {
+        return None;
+    }
+    let samples = Samples {
+        data: decode_filters(stream, &filters)?,
+        width: dimension(doc, dict, b"Width"),
+        height: dimension(doc, dict, b"Height"),
+        components,
+        bits,
+    };
+    samples.is_complete().then_some(samples)
+}
+
+// Couche alpha (0-255) d'un /SMask ou d'un /Mask explicite, ramenée aux dimensions de l'image
+fn mask_alpha(doc: &Document, dict: &Dictionary, width: usize, height: usize) -> Option<Vec<u8>> {
+    let (mask, soft) = match dict.get(b"SMask").map(|o| resolve(doc, o)) {
+        Ok(Object::Stream(smask)) => (smask, true),
+        _ => match dict.get(b"Mask").map(|o| resolve(doc, o)) {
+            Ok(Object::Stream(mask)) => (mask, false),
+            _ => return None,
+        },
+    };
+    let samples = if soft { raw_samples(doc, mask, 1)? } else { raw_samples(doc, &stencil(mask), 1)? };
+    let (min, max) = decode_ranges(doc, &mask.dict, 1, 1.0)[0];
+    let scale = samples.max_value() as f64;
+    let mut alpha = GrayImage::new(samples.width as u32, samples.height as u32);
+    for (x, y, pixel) in alpha.enumerate_pixels_mut() {
+        let value = min + samples.get(x as usize, y as usize, 0) as f64 * (max - min) / scale;
+        // Masque explicite : 1 masque la zone, comme un masque de pochoir ; masque doux : opacité
+        let opacity = if soft { value } else { 1.0 - value };
+        pixel.0[0] = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+    if alpha.width() as usize != width || alpha.height() as usize != height {
+        alpha = image::imageops::resize(&alpha, width as u32, height as u32, image::imageops::FilterType::Triangle);
+    }
+    Some(alpha.into_raw())
+}
+
+// Un /Mask explicite est un masque de pochoir 1 bit, même s'il ne porte pas /ImageMask
+fn stencil(mask: &Stream) -> Stream {
+    let mut dict = mask.dict.clone();
+    dict.set("ImageMask", true);
+    Stream::new(dict, mask.content.clone())
+}
+
+// Masque par couleur (/Mask [min1 max1 ...]) : comparé aux valeurs brutes, avant /Decode
+fn color_key(doc: &Document, dict: &Dictionary, components: usize) -> Option<Vec<(u32, u32)>> {
+    let array = dict.get(b"Mask").ok().and_then(|o| resolve(doc, o).as_array().ok())?;
+    (array.len() >= 2 * components).then(|| {
+        (0..components)
+            .map(|c| (as_f64(resolve(doc, &array[2 * c])) as u32, as_f64(resolve(doc, &array[2 * c + 1])) as u32))
+            .collect()
+    })
+}
+
+pub fn decode_image(doc: &Document, stream: &Stream) -> Option<DecodedImage> {
+    let dict = &stream.dict;
+    let filters = filter_names(doc, dict);
+    let is_dct = filters.last().is_some_and(|f| matches!(f.as_slice(), b"DCTDecode" | b"DCT"));
+    let image_mask = dict.get(b"ImageMask").and_then(|o| o.as_bool()).unwrap_or(false);
+    let space = dict.get(b"ColorSpace").ok().map(|cs| ColorSpace::from_object(doc, cs));
+    let has_mask = dict.has(b"SMask") || dict.has(b"Mask");
+
+    // JPEG 2000, CCITT et JBIG2 ne sont pas décodés : ils sont recopiés dans leur format de fichier
+    let generic = &filters[..filters.len().saturating_sub(1)];
+    match filters.last().map(Vec::as_slice) {
+        Some(b"JPXDecode") => {
+            let data = decode_filters(stream, generic)?;
+            let extension = image_containers::jpx_extension(&data);
+            return Some(DecodedImage::Encoded { data, extension });
+        }
+        Some(b"CCITTFaxDecode" | b"CCF") => {
+            let params = filter_params(doc, dict, filters.len() - 1);
+            let (min, max) = decode_ranges(doc, dict, 1, 1.0)[0];
+            let (width, height) = (dimension(doc, dict, b"Width") as u32, dimension(doc, dict, b"Height") as u32);
+            let data = image_containers::ccitt_tiff(&decode_filters(stream, generic)?, params, width, height, min > max);
+            return Some(DecodedImage::Encoded { data, extension: "tif" });
+        }
+        Some(b"JBIG2Decode") => {
+            let globals = filter_params(doc, dict, filters.len() - 1)
+                .and_then(|params| params.get(b"JBIG2Globals").ok())
+                .and_then(|o| resolve(doc, o).as_stream().ok())
+                .map(|globals| globals.decompressed_content().unwrap_or_else(|_| globals.content.clone()));
+            let data = image_containers::jbig2_file(globals.as_deref(), &decode_filters(stream, generic)?);
+            return Some(DecodedImage::Encoded { data, extension: "jb2" });
+        }
+        _ => {}
+    }
+
+    // Un JPEG gris ou RGB sans masque ni /Decode est recopié sans perte
+    if is_dct && filters.len() == 1 && !has_mask {
+        if let Some(space @ (ColorSpace::Gray | ColorSpace::Rgb)) = &space {
+            if has_default_decode(doc, dict, space.component_count()) {
+                return Some(DecodedImage::Encoded { data: stream.content.clone(), extension: "jpg" });
+            }
+        }
+    }
+
+    let components = if image_mask { 1 } else { space.as_ref().map(ColorSpace::component_count).unwrap_or(1) };
+    let samples = raw_samples(doc, stream, components)?;
+    let (width, height) = (samples.width, samples.height);
+    // L'espace d'un JPEG se déduit de ses composantes quand /ColorSpace est absent ou incohérent
+    let space = match space {
+        Some(space) if space.component_count() == samples.components => space,
+        _ => match samples.components {
+            1 => ColorSpace::Gray,
+            4 => ColorSpace::Cmyk,
+            _ => ColorSpace::Rgb,
+        },
+    };
+    let n = samples.components;
+
+    // Masque de pochoir : 0 peint (en noir, la couleur de remplissage n'étant pas connue ici), 1 transparent
+    if image_mask {
+        let (min, max) = decode_ranges(doc, dict, 1, 1.0)[0];
+        let mut rgba = RgbaImage::new(width as u32, height as u32);
+        for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+            let value = min + samples.get(x as usize, y as usize, 0) as f64 * (max - min);
+            pixel.0 = [0, 0, 0, if value < 0.5 { 255 } else { 0 }];
+        }
+        return Some(DecodedImage::Raster(DynamicImage::ImageRgba8(rgba)));
+    }
+
+    let default_max = if matches!(space, ColorSpace::Indexed { .. }) { samples.max_value() as f64 } else { 1.0 };
+    let ranges = decode_ranges(doc, dict, n, default_max);
+    let scale = samples.max_value() as f64;
+    let to_rgb = |raw: &[u32], components: &mut [f64]| {
+        for (c, value) in components.iter_mut().enumerate() {
+            let (min, max) = ranges[c];
+            *value = min + raw[c] as f64 * (max - min) / scale;
+        }
+        space.to_rgb(components).map(|rgb| [rgb.r, rgb.g, rgb.b]).unwrap_or([0, 0, 0])
+    };
+    // Une seule composante sur 8 bits au plus (gris, Indexed, Separation) : table de conversion précalculée
+    let lookup: Option<Vec<[u8; 3]>> = (n == 1 && samples.bits <= 8).then(|| (0..=samples.max_value()).map(|v| to_rgb(&[v], &mut [0.0])).collect());
+
+    let key = color_key(doc, dict, n);
+    let alpha = mask_alpha(doc, dict, width, height);
+    let gray = matches!(space, ColorSpace::Gray);
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    let mut raw = vec![0u32; n];
+    let mut components = vec![0.0; n];
+    for y in 0..height {
+        for x in 0..width {
+            for (c, value) in raw.iter_mut().enumerate() {
+                *value = samples.get(x, y, c);
+            }
+            let rgb = match &lookup {
+                Some(table) => table[raw[0] as usize],
+                None => to_rgb(&raw, &mut components),
+            };
+            let opacity = match (&key, &alpha) {
+                (Some(key), _) if raw.iter().zip(key).all(|(v, (min, max))| (min..=max).contains(&v)) => 0,
+                (_, Some(alpha)) => alpha[y * width + x],
+                _ => 255,
+            };
+            match (gray, key.is_some() || alpha.is_some()) {
+                (true, false) => pixels.push(rgb[0]),
+                (true, true) => pixels.extend_from_slice(&[rgb[0], opacity]),
+                (false, false) => pixels.extend_from_slice(&rgb),
+                (false, true) => pixels.extend_from_slice(&[rgb[0], rgb[1], rgb[2], opacity]),
+            }
+        }
+    }
+
+    let (w, h) = (width as u32, height as u32);
+    let image = match (gray, key.is_some() || alpha.is_some()) {
+        (true, false) => DynamicImage::ImageLuma8(GrayImage::from_raw(w, h, pixels)?),
+        (true, true) => DynamicImage::ImageLumaA8(GrayAlphaImage::from_raw(w, h, pixels)?),
+        (false, false) => DynamicImage::ImageRgb8(RgbImage::from_raw(w, h, pixels)?),
+        (false, true) => DynamicImage::ImageRgba8(RgbaImage::from_raw(w, h, pixels)?),
+    };
+    Some(DecodedImage::Raster(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn samples(data: &[u8], width: usize, height: usize, components: usize, bits: usize) -> Samples {
+        Samples { data: data.to_vec(), width, height, components, bits }
+    }
+
+    // Image non compressée de `width` × `height` pixels, décodée en RGB 8 bits
+    fn decode_rgb(doc: &Document, mut dict: Dictionary, data: &[u8], width: i64, height: i64) -> Vec<u8> {
+        dict.set("Subtype", "Image");
+        dict.set("Width", width);
+        dict.set("Height", height);
+        match decode_image(doc, &Stream::new(dict, data.to_vec())) {
+            Some(DecodedImage::Raster(image)) => image.to_rgb8().into_raw(),
+            _ => panic!("image non décodée"),
+        }
+    }
+
+    #[test]
+    fn samples_unpack_each_bit_depth() {
+        let one = samples(&[0b1010_0000, 0b0110_0000], 3, 2, 1, 1);
+        assert_eq!((0..3).map(|x| one.get(x, 0, 0)).collect::<Vec<_>>(), vec![1, 0, 1]);
+        // Chaque ligne commence sur un octet
+        assert_eq!((0..3).map(|x| one.get(x, 1, 0)).collect::<Vec<_>>(), vec![0, 1, 1]);
+
+        let two = samples(&[0b1001_0011], 4, 1, 1, 2);
+        assert_eq!((0..4).map(|x| two.get(x, 0, 0)).collect::<Vec<_>>(), vec![2, 1, 0, 3]);
+
+        let four = samples(&[0xAB, 0xC0], 3, 1, 1, 4);
+        assert_eq!((0..3).map(|x| four.get(x, 0, 0)).collect::<Vec<_>>(), vec![10, 11, 12]);
+
+        let sixteen = samples(&[0x12, 0x34, 0xFF, 0xFF, 0x00, 0x01], 1, 1, 3, 16);
+        assert_eq!((0..3).map(|c| sixteen.get(0, 0, c)).collect::<Vec<_>>(), vec![0x1234, 0xFFFF, 1]);
+        assert_eq!(sixteen.max_value(), 0xFFFF);
+        assert!(!samples(&[0xFF], 3, 2, 1, 1).is_complete());
+    }
+
+    #[test]
+    fn gray_images_with_decode_and_depth() {
+        let doc = Document::with_version("1.5");
+        let gray = |bits: i64| dictionary! {"ColorSpace" => "DeviceGray", "BitsPerComponent" => bits};
+        assert_eq!(decode_rgb(&doc, gray(8), &[0, 255], 2, 1), vec![0, 0, 0, 255, 255, 255]);
+        assert_eq!(decode_rgb(&doc, gray(16), &[0xFF, 0xFF, 0x80, 0x00], 2, 1), vec![255, 255, 255, 128, 128, 128]);
+        assert_eq!(decode_rgb(&doc, gray(2), &[0b0001_0000], 2, 1), vec![0, 0, 0, 85, 85, 85]);
+
+        // /Decode [1 0] inverse les valeurs
+        let mut inverted = gray(1);
+        inverted.set("Decode", vec![1.into(), 0.into()]);
+        assert_eq!(decode_rgb(&doc, inverted, &[0b0100_0000], 2, 1), vec![255, 255, 255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn cmyk_images_are_converted_to_rgb() {
+        let doc = Document::with_version("1.5");
+        let cmyk = dictionary! {"ColorSpace" => "DeviceCMYK", "BitsPerComponent" => 8};
+        assert_eq!(decode_rgb(&doc, cmyk.clone(), &[0, 255, 255, 0, 0, 0, 0, 0], 2, 1), vec![255, 0, 0, 255, 255, 255]);
+        // Valeurs inversées par /Decode, comme celles des JPEG CMYK d'Adobe
+        let mut inverted = cmyk;
+        inverted.set("Decode", (0..4).flat_map(|_| [1.into(), 0.into()]).collect::<Vec<Object>>());
+        assert_eq!(decode_rgb(&doc, inverted, &[255, 0, 0, 255], 1, 1), vec![255, 0, 0]);
+
+        let mut adobe = vec![0xFF, 0xD8, 0xFF, 0xEE, 0x00, 0x0E];
+        adobe.extend_from_slice(b"Adobe\x00\x64\x00\x00\x00\x00\x02");
+        assert!(has_adobe_marker(&adobe));
+        assert!(!has_adobe_marker(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0, 0]));
+    }
+
+    #[test]
+    fn indexed_images_use_their_palette() {
+        let doc = Document::with_version("1.5");
+        let palette = Object::String(vec![255, 0, 0, 0, 0, 255, 0, 128, 0], lopdf::StringFormat::Hexadecimal);
+        let indexed = dictionary! {
+            "ColorSpace" => vec![Object::Name(b"Indexed".to_vec()), Object::Name(b"DeviceRGB".to_vec()), 2.into(), palette],
+            "BitsPerComponent" => 4,
+        };
+        assert_eq!(decode_rgb(&doc, indexed, &[0x01, 0x20], 3, 1), vec![255, 0, 0, 0, 0, 255, 0, 128, 0]);
+    }
+}
@@ -0,0 +1,287 @@
+use lopdf::content::Content;
+use lopdf::{Dictionary, Object, Stream};
+
+// Clés abrégées du dictionnaire d'une image en ligne (`BI` ... `ID`)
+const ABBREVIATED_KEYS: [(&[u8], &str); 10] = [
+    (b"BPC", "BitsPerComponent"),
+    (b"CS", "ColorSpace"),
+    (b"D", "Decode"),
+    (b"DP", "DecodeParms"),
+    (b"F", "Filter"),
+    (b"H", "Height"),
+    (b"I", "Interpolate"),
+    (b"IM", "ImageMask"),
+    (b"L", "Length"),
+    (b"W", "Width"),
+];
+
+// Noms abrégés des filtres et des espaces de couleur
+const ABBREVIATED_NAMES: [(&[u8], &str); 11] = [
+    (b"AHx", "ASCIIHexDecode"),
+    (b"A85", "ASCII85Decode"),
+    (b"LZW", "LZWDecode"),
+    (b"Fl", "FlateDecode"),
+    (b"RL", "RunLengthDecode"),
+    (b"CCF", "CCITTFaxDecode"),
+    (b"DCT", "DCTDecode"),
+    (b"G", "DeviceGray"),
+    (b"RGB", "DeviceRGB"),
+    (b"CMYK", "DeviceCMYK"),
+    (b"I", "Indexed"),
+];
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(byte, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
+
+fn expand_name(name: &[u8]) -> Object {
+    match ABBREVIATED_NAMES.iter().find(|(short, _)| *short == name) {
+        Some((_, full)) => Object::Name(full.as_bytes().to_vec()),
+        None => Object::Name(name.to_vec()),
+    }
+}
+
+// Développe les noms abrégés d'une valeur de /Filter ou /ColorSpace (nom seul ou tableau)
+fn expand_value(value: Object) -> Object {
+    match value {
+        Object::Name(name) => expand_name(&name),
+        Object::Array(array) => Object::Array(
+            array
+                .into_iter()
+                .map(|item| match item {
+                    Object::Name(name) => expand_name(&name),
+                    other => other,
+                })
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+// Dictionnaire de l'image avec les clés et les noms complets d'un XObject image
+fn image_dictionary(operands: Vec<Object>) -> Dictionary {
+    let mut dict = Dictionary::new();
+    dict.set("Type", "XObject");
+    dict.set("Subtype", "Image");
+    let mut operands = operands.into_iter();
+    while let (Some(Object::Name(key)), Some(value)) = (operands.next(), operands.next()) {
+        let key = ABBREVIATED_KEYS
+            .iter()
+            .find(|(short, _)| *short == key.as_slice())
+            .map_or(key.clone(), |(_, full)| full.as_bytes().to_vec());
+        let value = match key.as_slice() {
+            b"Filter" | b"ColorSpace" => expand_value(value),
+            _ => value,
+        };
+        dict.set(key, value);
+    }
+    dict
+}
+
+// Taille des données non filtrées, lorsque l'espace de couleur est connu sans les ressources
+fn raw_length(dict: &Dictionary) -> Option<usize> {
+    if dict.has(b"Filter") {
+        return None;
+    }
+    let value = |key: &[u8]| dict.get(key).and_then(|o| o.as_i64()).ok().filter(|&v| v > 0).map(|v| v as usize);
+    let (width, height) = (value(b"Width")?, value(b"Height")?);
+    let (components, bits) = if dict.get(b"ImageMask").and_then(|o| o.as_bool()).unwrap_or(false) {
+        (1, 1)
+    } else {
+        let components = match dict.get(b"ColorSpace").ok()? {
+            Object::Name(name) => match name.as_slice() {
+                b"DeviceGray" | b"CalGray" => 1,
+                b"DeviceRGB" | b"CalRGB" => 3,
+                b"DeviceCMYK" => 4,
+                _ => return None,
+            },
+            Object::Array(array) if array.first().and_then(|o| o.as_name().ok()) == Some(b"Indexed") => 1,
+            _ => return None,
+        };
+        (components, value(b"BitsPerComponent")?)
+    };
+    width.checked_mul(components)?.checked_mul(bits)?.div_ceil(8).checked_mul(height)
+}
+
+// Position de l'opérateur `EI` terminant les données : `EI` isolé et suivi de contenu textuel,
+// pour ne pas s'arrêter sur deux octets « EI » fortuits au milieu de données binaires
+fn find_end_marker(content: &[u8], from: usize) -> Option<usize> {
+    (from..content.len().saturating_sub(1)).find(|&i| {
+        let before = i == from || is_whitespace(content[i - 1]);
+        let after = content.get(i + 2).map_or(true, |&b| !is_regular(b));
+        before
+            && after
+            && &content[i..i + 2] == b"EI"
+            && content[i + 2..].iter().take(32).all(|&b| is_whitespace(b) || (0x20..0x7F).contains(&b))
+    })
+}
+
+// Fin d'un jeton régulier commençant en `start`
+fn token_end(content: &[u8], start: usize) -> usize {
+    (start..content.len()).find(|&i| !is_regular(content[i])).unwrap_or(content.len())
+}
+
+// Saute une chaîne littérale (parenthèses imbriquées, caractères échappés) ; `start` est sur la parenthèse ouvrante
+fn skip_literal_string(content: &[u8], start: usize) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i < content.len() {
+        match content[i] {
+            b'\\' => i += 1,
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    content.len()
+}
+
+// Prochain jeton régulier (opérateur, nombre, mot-clé) à partir de `i`, début et fin : les commentaires, chaînes
+// littérales et hexadécimales et les noms sont sautés, pour ne pas prendre `/BI` ou `(BI)` pour l'opérateur
+fn next_keyword(content: &[u8], mut i: usize) -> Option<(usize, usize)> {
+    while i < content.len() {
+        match content[i] {
+            b'%' => i = (i..content.len()).find(|&j| matches!(content[j], b'\r' | b'\n')).unwrap_or(content.len()),
+            b'(' => i = skip_literal_string(content, i),
+            b'<' if content.get(i + 1) == Some(&b'<') => i += 2,
+            b'<' => i = (i..content.len()).find(|&j| content[j] == b'>').map_or(content.len(), |j| j + 1),
+            b'/' => i = token_end(content, i + 1),
+            b if is_regular(b) => return Some((i, token_end(content, i))),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+// Retire les images en ligne d'un flux de contenu, que `lopdf` ne sait pas analyser (les données binaires
+// interrompent le décodage). Chaque image devient l'opération `<rang> BI` et son flux, aux clés
+// développées, est renvoyé à ce rang.
+pub fn split_inline_images(content: &[u8]) -> (Vec<u8>, Vec<Stream>) {
+    let mut output = Vec::with_capacity(content.len());
+    let mut images = Vec::new();
+    let mut copied = 0;
+    let mut i = 0;
+    while let Some((start, end)) = next_keyword(content, i) {
+        i = end;
+        if &content[start..end] != b"BI" {
+            continue;
+        }
+        let Some((image, image_end)) = inline_image(content, end) else {
+            continue;
+        };
+        output.extend_from_slice(&content[copied..start]);
+        output.extend_from_slice(format!("{} BI", images.len()).as_bytes());
+        images.push(image);
+        copied = image_end;
+        i = image_end;
+    }
+    if images.is_empty() {
+        return (content.to_vec(), images);
+    }
+    output.extend_from_slice(&content[copied..]);
+    (output, images)
+}
+
+// Image en ligne dont le dictionnaire commence en `start` (après `BI`) ; renvoie aussi la fin de `EI`
+fn inline_image(content: &[u8], start: usize) -> Option<(Stream, usize)> {
+    // Dictionnaire jusqu'à l'opérateur `ID`, hors chaînes et noms (une table /Indexed peut contenir « ID »)
+    let mut i = start;
+    let id_start = loop {
+        let (token_start, token_end) = next_keyword(content, i)?;
+        if &content[token_start..token_end] == b"ID" {
+            break token_start;
+        }
+        i = token_end;
+    };
+    let mut operands = content[start..id_start].to_vec();
+    operands.extend_from_slice(b" ID");
+    let operation = Content::decode(&operands).ok()?.operations.pop()?;
+    let dict = image_dictionary(operation.operands);
+
+    // Un seul blanc sépare `ID` des données
+    let data_start = (id_start + 3).min(content.len());
+    let data_end = dict
+        .get(b"Length")
+        .and_then(|o| o.as_i64())
+        .ok()
+        .map(|length| length.max(0) as usize)
+        .or_else(|| raw_length(&dict))
+        .and_then(|length| data_start.checked_add(length))
+        .map(|end| end.min(content.len()));
+    let (data_end, ei) = match data_end.and_then(|end| {
+        find_end_marker(content, end)
+            .filter(|&ei| content[end..ei].iter().all(|&b| is_whitespace(b)))
+            .map(|ei| (end, ei))
+    }) {
+        Some(found) => found,
+        None => {
+            let ei = find_end_marker(content, data_start)?;
+            // Le blanc qui précède `EI` ne fait pas partie des données
+            (ei.saturating_sub(1).max(data_start), ei)
+        }
+    };
+    Some((Stream::new(dict, content[data_start..data_end].to_vec()), ei + 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_strings_are_not_operators() {
+        let content = b"/BI gs (BI) Tj <4249> Tj [(a BI b)] TJ % BI\nBI /W 1 /H 1 /CS /G /BPC 8 ID \x80 EI";
+        let (output, images) = split_inline_images(content);
+        assert_eq!(output, b"/BI gs (BI) Tj <4249> Tj [(a BI b)] TJ % BI\n0 BI".to_vec());
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].content, vec![0x80]);
+    }
+
+    #[test]
+    fn split_image_with_abbreviated_keys() {
+        let content = b"q (BI) Tj BI /W 2 /H 1 /CS /G /BPC 8 ID \x00\xff EI Q";
+        let (output, images) = split_inline_images(content);
+        assert_eq!(output, b"q (BI) Tj 0 BI Q".to_vec());
+        assert_eq!(images.len(), 1);
+        let image = &images[0];
+        assert_eq!(image.content, vec![0x00, 0xFF]);
+        assert_eq!(image.dict.get(b"Width").and_then(|o| o.as_i64()).ok(), Some(2));
+        assert_eq!(image.dict.get(b"ColorSpace").and_then(|o| o.as_name()).ok(), Some(&b"DeviceGray"[..]));
+    }
+
+    #[test]
+    fn find_end_marker_without_length() {
+        // Filtre inconnu : la taille n'est pas calculable, les données s'arrêtent au `EI` isolé
+        let content = b"BI /W 4 /H 4 /F /DCT ID ABEIx\xd9 EI\n0 g";
+        let (output, images) = split_inline_images(content);
+        assert_eq!(output, b"0 BI\n0 g".to_vec());
+        assert_eq!(images[0].content, b"ABEIx\xd9".to_vec());
+        assert_eq!(images[0].dict.get(b"Filter").and_then(|o| o.as_name()).ok(), Some(&b"DCTDecode"[..]));
+    }
+
+    #[test]
+    fn oversized_dimensions_fall_back_to_end_marker() {
+        // Taille calculée ou /Length hors de portée : on s'en remet au `EI` isolé
+        let huge = format!("BI /W {0} /H {0} /CS /RGB /BPC 16 ID \x01\x02 EI Q", i64::MAX);
+        let (output, images) = split_inline_images(huge.as_bytes());
+        assert_eq!(output, b"0 BI Q".to_vec());
+        assert_eq!(images[0].content, vec![0x01, 0x02]);
+
+        let long = format!("BI /W 1 /H 1 /CS /G /BPC 8 /L {} ID \x03 EI Q", i64::MAX);
+        let (_, images) = split_inline_images(long.as_bytes());
+        assert_eq!(images[0].content, vec![0x03]);
+    }
+}
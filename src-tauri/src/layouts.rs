@@ -0,0 +1,161 @@
+use crate::blocks;
+use crate::dxf_patch::Patches;
+use crate::names;
+use crate::pdf_converter::Sheet;
+use dxf::entities::Entity;
+use dxf::objects::{Dictionary, Layout, Object, ObjectType};
+use dxf::{CodePair, Drawing, Handle};
+use std::collections::HashMap;
+
+// Noms des présentations d'après les libellés de page : caractères interdits remplacés, « Model » réservé,
+// doublons (la casse n'est pas distinguée) numérotés « A-1 (2) »
+fn layout_names(sheets: &[Sheet]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(sheets.len());
+    for (index, sheet) in sheets.iter().enumerate() {
+        let label = names::table_name(&sheet.label);
+        let label = match label.trim() {
+            "" => format!("Page {}", index + 1),
+            label if label.eq_ignore_ascii_case("Model") => format!("{} {}", label, index + 1),
+            label => label.to_string(),
+        };
+        let taken = |name: &str| name.eq_ignore_ascii_case("Model") || names.iter().any(|n| n.eq_ignore_ascii_case(name));
+        let name = (1..)
+            .map(|n| if n == 1 { label.clone() } else { format!("{} ({})", label, n) })
+            .find(|name| !taken(name))
+            .unwrap_or(label);
+        names.push(name);
+    }
+    names
+}
+
+// Groupes AcDbPlotSettings et AcDbLayout d'une présentation dont le papier (en mm, à l'échelle 1:1) a la taille de la feuille
+fn layout_pairs(name: &str, tab_order: i16, block_record: Handle, (width, height): (f64, f64)) -> Vec<CodePair> {
+    let mut pairs = vec![
+        CodePair::new_str(100, "AcDbPlotSettings"),
+        CodePair::new_str(1, ""),
+        CodePair::new_str(2, "none_device"),
+        CodePair::new_str(4, ""),
+        CodePair::new_str(6, ""),
+    ];
+    // Marges, format du papier, origine et fenêtre de tracé
+    for (code, value) in [(40, 0.0), (41, 0.0), (42, 0.0), (43, 0.0), (44, width), (45, height), (46, 0.0), (47, 0.0)] {
+        pairs.push(CodePair::new_f64(code, value));
+    }
+    for (code, value) in [(48, 0.0), (49, 0.0), (140, 0.0), (141, 0.0), (142, 1.0), (143, 1.0)] {
+        pairs.push(CodePair::new_f64(code, value));
+    }
+    pairs.extend([
+        CodePair::new_i16(70, 0),
+        CodePair::new_i16(72, 1), // unités du papier : mm
+        CodePair::new_i16(73, 0),
+        CodePair::new_i16(74, 5), // tracé de la présentation
+        CodePair::new_str(7, ""),
+        CodePair::new_i16(75, 16), // échelle 1:1
+        CodePair::new_f64(147, 1.0),
+        CodePair::new_f64(148, 0.0),
+        CodePair::new_f64(149, 0.0),
+        CodePair::new_str(100, "AcDbLayout"),
+        CodePair::new_string(1, name),
+        CodePair::new_i16(70, 1), // PSLTSCALE
+        CodePair::new_i16(71, tab_order),
+    ]);
+    // Limites, point de base, étendues et élévation, puis SCU général
+    for (code, value) in [(10, 0.0), (20, 0.0), (11, width), (21, height), (12, 0.0), (22, 0.0), (32, 0.0)] {
+        pairs.push(CodePair::new_f64(code, value));
+    }
+    for (code, value) in [(14, 0.0), (24, 0.0), (34, 0.0), (15, width), (25, height), (35, 0.0), (146, 0.0)] {
+        pairs.push(CodePair::new_f64(code, value));
+    }
+    for (code, value) in [(13, 0.0), (23, 0.0), (33, 0.0), (16, 1.0), (26, 0.0), (36, 0.0), (17, 0.0), (27, 1.0), (37, 0.0)] {
+        pairs.push(CodePair::new_f64(code, value));
+    }
+    pairs.push(CodePair::new_i16(76, 0));
+    pairs.push(CodePair::new_string(330, &block_record.as_string()));
+    pairs
+}
+
+fn block_record_handle(drawing: &Drawing, name: &str) -> Option<Handle> {
+    drawing.block_records().find(|record| record.name.eq_ignore_ascii_case(name)).map(|record| record.handle)
+}
+
+// Présentation rangée dans le dictionnaire ACAD_LAYOUT et reliée à son bloc d'espace papier
+fn add_layout(drawing: &mut Drawing, layout_dict: Handle, name: &str, tab_order: i16, record: Handle, size: (f64, f64), patches: &mut Patches) -> Handle {
+    let mut object = Object::new(ObjectType::Layout(Layout { layout_name: name.to_string(), tab_order: tab_order as i32, ..Default::default() }));
+    object.common.__owner_handle = layout_dict;
+    let handle = drawing.add_object(object).common.handle;
+    patches.layouts.insert(handle.0, layout_pairs(name, tab_order, record, size));
+    if let Some(block_record) = drawing.block_records_mut().find(|block_record| block_record.handle == record) {
+        block_record.__layout_handle = handle;
+    }
+    handle
+}
+
+// Range chaque feuille dans sa présentation (R2000+) : les entités de la première restent dans la section ENTITIES,
+// en espace papier (*Paper_Space), celles des suivantes passent dans les blocs *Paper_Space0, *Paper_Space1...
+// `sheet_starts` donne le rang de la première entité de chaque feuille, ajoutées feuille après feuille.
+pub fn add_layouts(drawing: &mut Drawing, root: Handle, sheets: &[Sheet], sheet_starts: &[usize], final_scale: f64, patches: &mut Patches) {
+    let (Some(model_record), Some(paper_record)) = (block_record_handle(drawing, "*Model_Space"), block_record_handle(drawing, "*Paper_Space")) else {
+        return;
+    };
+    let names = layout_names(sheets);
+    let size = |sheet: &Sheet| (sheet.size.0 * final_scale.abs(), sheet.size.1 * final_scale.abs());
+
+    // Entités des feuilles suivant la première, retirées de la fin du dessin
+    let mut sheet_entities: Vec<Vec<Entity>> = Vec::with_capacity(sheets.len());
+    for &start in sheet_starts.iter().skip(1).rev() {
+        sheet_entities.push(blocks::take_entities(drawing, start));
+    }
+    sheet_entities.reverse();
+
+    let layout_dict = drawing.add_object(Object::new(ObjectType::Dictionary(Dictionary::default()))).common.handle;
+    let mut entries = HashMap::new();
+    let model_size = sheets.first().map(size).unwrap_or_default();
+    entries.insert(String::from("Model"), add_layout(drawing, layout_dict, "Model", 0, model_record, model_size, patches));
+
+    for entity in drawing.entities_mut() {
+        entity.common.is_in_paper_space = true;
+        entity.common.__owner_handle = paper_record;
+    }
+
+    for (index, (sheet, name)) in sheets.iter().zip(&names).enumerate() {
+        let record = match index {
+            0 => paper_record,
+            _ => {
+                let entities = std::mem::take(&mut sheet_entities[index - 1]);
+                match blocks::add_block(drawing, &format!("*Paper_Space{}", index - 1), entities, true, patches) {
+                    Some(record) => record,
+                    None => continue,
+                }
+            }
+        };
+        entries.insert(name.clone(), add_layout(drawing, layout_dict, name, index as i16 + 1, record, size(sheet), patches));
+    }
+
+    for object in drawing.objects_mut() {
+        if let ObjectType::Dictionary(dict) = &mut object.specific {
+            if object.common.handle == root {
+                dict.value_handles.insert(String::from("ACAD_LAYOUT"), layout_dict);
+            } else if object.common.handle == layout_dict {
+                dict.value_handles = std::mem::take(&mut entries);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheets(labels: &[&str]) -> Vec<Sheet> {
+        labels
+            .iter()
+            .map(|label| Sheet { label: label.to_string(), size: (595.0, 842.0), paths_end: 0, texts_end: 0, images_end: 0, inserts_end: 0 })
+            .collect()
+    }
+
+    #[test]
+    fn names_from_page_labels() {
+        assert_eq!(layout_names(&sheets(&["A-1", "A-2", "a-1", ""])), vec!["A-1", "A-2", "a-1 (2)", "Page 4"]);
+        assert_eq!(layout_names(&sheets(&["Plan: RDC/1", "model"])), vec!["Plan_ RDC_1", "model 2"]);
+    }
+}
@@ -1,15 +1,73 @@
+mod arc_fitting;
+mod blocks;
+mod clipping;
+mod color;
+mod dxf_patch;
+mod font;
+mod glyph_outlines;
+mod hatch;
+mod image_containers;
+mod images;
+mod inline_images;
+mod layouts;
+mod names;
+mod optional_content;
+mod page_selection;
 mod pdf_converter;
+mod pdf_objects;
+mod text;
 
 use std::path::Path;
 use lopdf::Document;
 
+// Convertit les pages données en un fichier DXF ; `Ok(false)` lorsqu'elles ne contiennent rien d'exportable.
+// Les images sont nommées d'après `images_base_path` (le DXF principal, même lorsque chaque page a son fichier).
+fn convert_pages(
+    doc: &Document,
+    pages: &[u32],
+    output_dxf_path: &Path,
+    images_base_path: &Path,
+    scale_factor: f64,
+    unit: &str,
+    options: &pdf_converter::ConversionOptions,
+) -> Result<bool, String> {
+    let output_path_str = output_dxf_path.to_str().ok_or("Invalid output path")?;
+
+    // Extraction des vecteurs et des textes
+    let content = pdf_converter::extract_pdf_content(doc, options, pages);
+
+    if content.is_empty() {
+        return Ok(false);
+    }
+
+    // Extraction des images (fichiers référencés par les entités IMAGE du DXF)
+    let image_files = pdf_converter::extract_images(doc, &content, images_base_path);
+
+    // Génération du DXF avec facteur d'échelle et unité
+    pdf_converter::generate_dxf(&content, &image_files, output_path_str, scale_factor, unit, options)
+        .map_err(|e| format!("Erreur lors de la génération du DXF : {:?}", e))?;
+
+    Ok(true)
+}
+
 #[tauri::command]
-fn convert_pdf(input_path: String, scale_factor: f64, unit: String) -> Result<String, String> {
+fn convert_pdf(
+    input_path: String,
+    scale_factor: f64,
+    unit: String,
+    options: Option<pdf_converter::ConversionOptions>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
     let input_pdf_path = Path::new(&input_path);
     if !input_pdf_path.exists() {
         return Err(format!("Le fichier '{}' est introuvable.", input_path));
     }
 
+    // Les présentations (LAYOUT, BLOCK_RECORD) n'existent qu'à partir de R2000
+    if options.page_layout == pdf_converter::PageLayout::Layouts && options.dxf_version == pdf_converter::DxfVersion::R12 {
+        return Err("Une présentation par page nécessite un DXF R2000 ou plus récent.".to_string());
+    }
+
     let output_dxf_path = input_pdf_path.with_extension("dxf");
     let output_path_str = output_dxf_path.to_str().ok_or("Invalid output path")?.to_string();
 
@@ -17,19 +75,29 @@ fn convert_pdf(input_path: String, scale_factor: f64, unit: String) -> Result<St
     let doc = Document::load(input_pdf_path)
         .map_err(|e| format!("Erreur lors du décodage du PDF : {:?}", e))?;
 
-    // Extraction des images
-    pdf_converter::extract_images(&doc, &output_dxf_path);
-
-    // Extraction des vecteurs
-    let lines = pdf_converter::extract_pdf_paths(&doc);
+    // Seules les pages sélectionnées sont analysées, disposées et leurs images extraites
+    let pages: Vec<u32> = match options.pages.as_deref().map(str::trim) {
+        Some(selection) if !selection.is_empty() => page_selection::select_pages(&doc, selection)?,
+        _ => doc.get_pages().keys().copied().collect(),
+    };
+    let nothing_found = "Aucun vecteur graphique ni texte n'a été trouvé dans le PDF.".to_string();
 
-    if lines.is_empty() {
-        return Err("Aucun vecteur graphique n'a été trouvé dans le PDF.".to_string());
+    // Un DXF par page : le chemin renvoyé est celui de la première page convertie
+    if options.page_layout == pdf_converter::PageLayout::Separate {
+        let stem = input_pdf_path.file_stem().and_then(|s| s.to_str()).unwrap_or("document");
+        let mut first_output = None;
+        for &page in &pages {
+            let page_dxf_path = output_dxf_path.with_file_name(format!("{}_p{}.dxf", stem, page));
+            if convert_pages(&doc, &[page], &page_dxf_path, &output_dxf_path, scale_factor, &unit, &options)? && first_output.is_none() {
+                first_output = Some(page_dxf_path.to_str().ok_or("Invalid output path")?.to_string());
+            }
+        }
+        return first_output.ok_or(nothing_found);
     }
 
-    // Génération du DXF avec facteur d'échelle et unité
-    pdf_converter::generate_dxf(&lines, &output_path_str, scale_factor, &unit)
-        .map_err(|e| format!("Erreur lors de la génération du DXF : {:?}", e))?;
+    if !convert_pages(&doc, &pages, &output_dxf_path, &output_dxf_path, scale_factor, &unit, &options)? {
+        return Err(nothing_found);
+    }
 
     Ok(output_path_str)
 }
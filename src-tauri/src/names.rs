@@ -0,0 +1,31 @@
+// Noms tirés du PDF (calques, libellés de page, ressources) rendus acceptables pour le DXF ou le système de fichiers
+
+// Caractères refusés dans un nom de table DXF (calque, présentation)
+const FORBIDDEN_CHARACTERS: &str = "<>/\\\":;?*|,=`";
+
+// Chaque caractère refusé par `allowed` est remplacé par « _ »
+fn sanitize(name: &str, allowed: impl Fn(char) -> bool) -> String {
+    name.chars().map(|c| if allowed(c) { c } else { '_' }).collect()
+}
+
+// Nom de calque ou de présentation : caractères interdits et caractères de contrôle remplacés
+pub fn table_name(name: &str) -> String {
+    sanitize(name, |c| !FORBIDDEN_CHARACTERS.contains(c) && !c.is_control())
+}
+
+// Nom limité aux caractères admis en R12 (lettres et chiffres ASCII, `$`, `-`, `_`), utilisable aussi dans un nom de fichier
+pub fn r12_name(name: &str) -> String {
+    sanitize(name, |c| c.is_ascii_alphanumeric() || matches!(c, '$' | '-' | '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forbidden_characters_are_replaced() {
+        assert_eq!(table_name("Plan: RDC/1\tbis"), "Plan_ RDC_1_bis");
+        assert_eq!(table_name("Coupe A-A (é)"), "Coupe A-A (é)");
+        assert_eq!(r12_name("Porte d'entrée $1"), "Porte_d_entr_e_$1");
+    }
+}
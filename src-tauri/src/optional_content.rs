@@ -0,0 +1,158 @@
+use crate::names;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::{HashMap, HashSet};
+
+// Calque PDF (groupe de contenu optionnel, OCG) sur lequel un chemin a été dessiné
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentLayer {
+    pub name: String,
+    // Visibilité par défaut (/OCProperties /D) : un calque masqué devient un calque DXF désactivé
+    pub visible: bool,
+}
+
+// Groupes de contenu optionnel déclarés dans le catalogue (/OCProperties)
+#[derive(Debug, Default)]
+pub struct OptionalContent {
+    groups: HashMap<ObjectId, ContentLayer>,
+}
+
+// Chaîne de texte PDF : UTF-16BE avec BOM, sinon PDFDocEncoding (approché par Latin-1)
+pub fn decode_text_string(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = bytes[2..].chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|&b| b as char).collect()
+    }
+}
+
+// Les caractères interdits dans un nom de calque DXF sont remplacés
+fn layer_name(name: &str) -> String {
+    let cleaned = names::table_name(name.trim());
+    if cleaned.is_empty() { String::from("OCG") } else { cleaned }
+}
+
+fn object_ids(obj: Option<&Object>) -> Vec<ObjectId> {
+    match obj {
+        Some(Object::Array(array)) => array.iter().filter_map(|o| o.as_reference().ok()).collect(),
+        Some(Object::Reference(id)) => vec![*id],
+        _ => Vec::new(),
+    }
+}
+
+impl OptionalContent {
+    pub fn from_document(doc: &Document) -> Self {
+        let mut optional_content = OptionalContent::default();
+        let properties = doc
+            .catalog()
+            .ok()
+            .and_then(|catalog| catalog.get(b"OCProperties").ok())
+            .and_then(|o| doc.dereference(o).ok())
+            .and_then(|(_, o)| o.as_dict().ok());
+        let properties = match properties {
+            Some(properties) => properties,
+            None => return optional_content,
+        };
+
+        // Configuration par défaut : état de base, puis listes /ON et /OFF
+        let config = properties
+            .get(b"D")
+            .ok()
+            .and_then(|o| doc.dereference(o).ok())
+            .and_then(|(_, o)| o.as_dict().ok());
+        let base_off = config
+            .and_then(|d| d.get(b"BaseState").and_then(|o| o.as_name()).ok())
+            .map(|state| state == b"OFF")
+            .unwrap_or(false);
+        let list = |key: &[u8]| -> HashSet<ObjectId> {
+            let obj = config.and_then(|d| d.get(key).ok()).and_then(|o| doc.dereference(o).ok()).map(|(_, o)| o);
+            object_ids(obj).into_iter().collect()
+        };
+        let (on, off) = (list(b"ON"), list(b"OFF"));
+
+        let ocgs = properties.get(b"OCGs").ok().and_then(|o| doc.dereference(o).ok()).map(|(_, o)| o);
+        for id in object_ids(ocgs) {
+            if let Ok(group) = doc.get_dictionary(id) {
+                let name = group
+                    .get(b"Name")
+                    .ok()
+                    .and_then(|o| doc.dereference(o).ok())
+                    .and_then(|(_, o)| o.as_str().ok())
+                    .map(decode_text_string)
+                    .unwrap_or_default();
+                let visible = if base_off { on.contains(&id) } else { !off.contains(&id) };
+                optional_content.groups.insert(id, ContentLayer { name: layer_name(&name), visible });
+            }
+        }
+        optional_content
+    }
+
+    // Calque désigné par un OCG ou par un dictionnaire d'appartenance (OCMD, premier groupe retenu)
+    pub fn layer_for(&self, doc: &Document, obj: &Object) -> Option<ContentLayer> {
+        let id = obj.as_reference().ok()?;
+        if let Some(layer) = self.groups.get(&id) {
+            return Some(layer.clone());
+        }
+        let dict = doc.get_dictionary(id).ok()?;
+        match dict.get(b"Type").and_then(|o| o.as_name()).ok()? {
+            b"OCMD" => object_ids(dict.get(b"OCGs").ok()).iter().find_map(|id| self.groups.get(id).cloned()),
+            _ => None,
+        }
+    }
+
+    // Opérande de `BDC /OC` : nom d'une entrée de /Properties dans les ressources
+    pub fn layer_for_property(&self, doc: &Document, resources: Option<&Dictionary>, name: &[u8]) -> Option<ContentLayer> {
+        let entry = resources?
+            .get(b"Properties")
+            .ok()
+            .and_then(|o| doc.dereference(o).ok())
+            .and_then(|(_, o)| o.as_dict().ok())?
+            .get(name)
+            .ok()?;
+        self.layer_for(doc, entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    // Document à trois calques : « Murs » visible, « Cotes » masqué par /OFF, « Notes » dans un OCMD
+    fn document(base_state: &str) -> (Document, Dictionary) {
+        let mut doc = Document::with_version("1.5");
+        let walls = doc.add_object(dictionary! {"Type" => "OCG", "Name" => Object::string_literal("Murs")});
+        let dimensions = doc.add_object(dictionary! {"Type" => "OCG", "Name" => Object::string_literal("Cotes")});
+        let notes = doc.add_object(dictionary! {"Type" => "OCG", "Name" => Object::string_literal("Notes / Revisions")});
+        let membership = doc.add_object(dictionary! {"Type" => "OCMD", "OCGs" => vec![notes.into()]});
+        let config = dictionary! {"BaseState" => base_state, "ON" => vec![walls.into()], "OFF" => vec![dimensions.into()]};
+        let properties = dictionary! {"OCGs" => vec![walls.into(), dimensions.into(), notes.into()], "D" => config};
+        let catalog_id = doc.add_object(dictionary! {"Type" => "Catalog", "OCProperties" => properties});
+        doc.trailer.set("Root", catalog_id);
+        let resources = dictionary! {"Properties" => dictionary! {"MC0" => walls, "MC1" => dimensions, "MC2" => membership}};
+        (doc, resources)
+    }
+
+    fn layer(doc: &Document, resources: &Dictionary, name: &[u8]) -> Option<(String, bool)> {
+        let optional_content = OptionalContent::from_document(doc);
+        optional_content.layer_for_property(doc, Some(resources), name).map(|layer| (layer.name, layer.visible))
+    }
+
+    #[test]
+    fn groups_are_named_and_hidden_from_the_default_configuration() {
+        let (doc, resources) = document("ON");
+        assert_eq!(layer(&doc, &resources, b"MC0"), Some((String::from("Murs"), true)));
+        assert_eq!(layer(&doc, &resources, b"MC1"), Some((String::from("Cotes"), false)));
+        // Un OCMD désigne le calque de son premier groupe ; le nom est nettoyé pour le DXF
+        assert_eq!(layer(&doc, &resources, b"MC2"), Some((String::from("Notes _ Revisions"), true)));
+        assert_eq!(layer(&doc, &resources, b"MC9"), None);
+    }
+
+    #[test]
+    fn base_state_off_hides_unlisted_groups() {
+        let (doc, resources) = document("OFF");
+        assert_eq!(layer(&doc, &resources, b"MC0"), Some((String::from("Murs"), true)));
+        assert_eq!(layer(&doc, &resources, b"MC1"), Some((String::from("Cotes"), false)));
+        assert_eq!(layer(&doc, &resources, b"MC2"), Some((String::from("Notes _ Revisions"), false)));
+    }
+}
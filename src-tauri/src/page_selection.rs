@@ -0,0 +1,236 @@
+use crate::optional_content::decode_text_string;
+use crate::pdf_objects::resolve;
+use lopdf::{Dictionary, Document};
+
+// Entrées (index de page, dictionnaire de libellé) d'un arbre de nombres /PageLabels, /Kids compris
+fn label_ranges<'a>(doc: &'a Document, node: &'a Dictionary, depth: u32, ranges: &mut Vec<(i64, &'a Dictionary)>) {
+    if let Ok(nums) = node.get(b"Nums").map(|o| resolve(doc, o)).and_then(|o| o.as_array()) {
+        for pair in nums.chunks_exact(2) {
+            if let (Ok(index), Ok(label)) = (resolve(doc, &pair[0]).as_i64(), resolve(doc, &pair[1]).as_dict()) {
+                ranges.push((index, label));
+            }
+        }
+    }
+    if depth > 32 {
+        return;
+    }
+    if let Ok(kids) = node.get(b"Kids").map(|o| resolve(doc, o)).and_then(|o| o.as_array()) {
+        for kid in kids {
+            if let Ok(kid) = resolve(doc, kid).as_dict() {
+                label_ranges(doc, kid, depth + 1, ranges);
+            }
+        }
+    }
+}
+
+// Chiffres romains jusqu'à 3999 ; au-delà, numéro décimal
+fn roman(mut n: i64) -> String {
+    if n > 3999 {
+        return n.to_string();
+    }
+    const NUMERALS: [(i64, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+// Style /A : A à Z, puis AA à ZZ, AAA... ; au-delà de 10 lettres, numéro décimal
+fn letters(n: i64) -> String {
+    if n > 26 * 10 {
+        return n.to_string();
+    }
+    let n = n.max(1) - 1;
+    let letter = (b'A' + (n % 26) as u8) as char;
+    letter.to_string().repeat((n / 26 + 1) as usize)
+}
+
+// Libellés des pages (`/PageLabels` du catalogue), dans l'ordre des pages ; vide sans libellés
+pub fn page_labels(doc: &Document, page_count: usize) -> Vec<String> {
+    let Some(root) = doc.catalog().ok().and_then(|catalog| catalog.get(b"PageLabels").ok()).and_then(|o| resolve(doc, o).as_dict().ok()) else {
+        return Vec::new();
+    };
+    let mut ranges = Vec::new();
+    label_ranges(doc, root, 0, &mut ranges);
+    ranges.sort_by_key(|&(index, _)| index);
+
+    (0..page_count as i64)
+        .map(|index| {
+            let Some(&(first, label)) = ranges.iter().rev().find(|&&(first, _)| first <= index) else {
+                return (index + 1).to_string();
+            };
+            let prefix = label
+                .get(b"P")
+                .ok()
+                .and_then(|o| resolve(doc, o).as_str().ok())
+                .map(decode_text_string)
+                .unwrap_or_default();
+            let start = label.get(b"St").and_then(|o| o.as_i64()).unwrap_or(1);
+            let Some(number) = index.checked_sub(first).and_then(|offset| start.checked_add(offset)) else {
+                return prefix;
+            };
+            let numbering = match label.get(b"S").and_then(|o| o.as_name()) {
+                Ok(b"D") => number.to_string(),
+                Ok(b"R") => roman(number),
+                Ok(b"r") => roman(number).to_lowercase(),
+                Ok(b"A") => letters(number),
+                Ok(b"a") => letters(number).to_lowercase(),
+                _ => String::new(),
+            };
+            prefix + &numbering
+        })
+        .collect()
+}
+
+// Une borne de sélection : libellé de page exact, numéro physique (à partir de 1) ou `last`
+fn page_number(bound: &str, labels: &[String], page_count: u32) -> Result<u32, String> {
+    if let Some(index) = labels.iter().position(|label| label == bound) {
+        return Ok(index as u32 + 1);
+    }
+    if bound.eq_ignore_ascii_case("last") {
+        return Ok(page_count);
+    }
+    match bound.parse::<u32>() {
+        Ok(number) if (1..=page_count).contains(&number) => Ok(number),
+        Ok(number) => Err(format!("La page {} n'existe pas (le document en compte {}).", number, page_count)),
+        Err(_) => Err(format!("Page « {} » introuvable : ni numéro, ni libellé de page.", bound)),
+    }
+}
+
+// Pages désignées par une sélection telle que « 1,3-5,last » ou « iv,A-3 », dans l'ordre de la sélection et
+// sans doublons. Un libellé de page (/PageLabels) l'emporte sur un numéro physique identique.
+pub fn select_pages(doc: &Document, selection: &str) -> Result<Vec<u32>, String> {
+    let page_count = doc.get_pages().len() as u32;
+    let labels = page_labels(doc, page_count as usize);
+    let mut pages = Vec::new();
+
+    for item in selection.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        // Un libellé peut contenir un tiret (« A-3 ») : il est reconnu avant d'être lu comme un intervalle,
+        // dont les bornes sont cherchées de part et d'autre de chaque tiret (« A-3-A-5 »)
+        let range = match page_number(item, &labels, page_count) {
+            Ok(number) => vec![number],
+            Err(error) => {
+                let bounds = item.match_indices('-').find_map(|(at, _)| {
+                    let first = page_number(item[..at].trim(), &labels, page_count).ok()?;
+                    let last = page_number(item[at + 1..].trim(), &labels, page_count).ok()?;
+                    Some((first, last))
+                });
+                let Some((first, last)) = bounds else {
+                    // Intervalle simple (« 1-50 ») : l'erreur porte sur la borne fautive
+                    if let Some((first, last)) = item.split_once('-') {
+                        page_number(first.trim(), &labels, page_count)?;
+                        page_number(last.trim(), &labels, page_count)?;
+                    }
+                    return Err(error);
+                };
+                if first <= last {
+                    (first..=last).collect()
+                } else {
+                    (last..=first).rev().collect()
+                }
+            }
+        };
+        for number in range {
+            if !pages.contains(&number) {
+                pages.push(number);
+            }
+        }
+    }
+
+    if pages.is_empty() {
+        return Err(format!("Sélection de pages vide : « {} ».", selection));
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Object};
+
+    // Document de `count` pages vides, avec un arbre /PageLabels facultatif
+    fn document(count: usize, labels: Option<Vec<Object>>) -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let kids: Vec<Object> = (0..count)
+            .map(|_| Object::Reference(doc.add_object(dictionary! {"Type" => "Page", "Parent" => pages_id})))
+            .collect();
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {"Type" => "Pages", "Kids" => kids, "Count" => count as i64}));
+        let mut catalog = dictionary! {"Type" => "Catalog", "Pages" => pages_id};
+        if let Some(nums) = labels {
+            catalog.set("PageLabels", dictionary! {"Nums" => nums});
+        }
+        let catalog_id = doc.add_object(catalog);
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    fn labelled() -> Document {
+        document(
+            5,
+            Some(vec![
+                0.into(),
+                Object::Dictionary(dictionary! {"S" => "r"}),
+                2.into(),
+                Object::Dictionary(dictionary! {"S" => "D", "P" => Object::string_literal("A-")}),
+            ]),
+        )
+    }
+
+    #[test]
+    fn numbers_ranges_and_last() {
+        let doc = document(6, None);
+        assert_eq!(select_pages(&doc, "1,3-5,last"), Ok(vec![1, 3, 4, 5, 6]));
+        assert_eq!(select_pages(&doc, " 5-3 , 2,4"), Ok(vec![5, 4, 3, 2]));
+        assert_eq!(select_pages(&doc, "2-LAST"), Ok(vec![2, 3, 4, 5, 6]));
+    }
+
+    #[test]
+    fn invalid_selections() {
+        let doc = document(6, None);
+        assert!(select_pages(&doc, "9").is_err());
+        assert!(select_pages(&doc, "2-9").is_err());
+        assert!(select_pages(&doc, "iv").is_err());
+        assert!(select_pages(&doc, " , ").is_err());
+    }
+
+    #[test]
+    fn page_labels_and_label_ranges() {
+        let doc = labelled();
+        assert_eq!(page_labels(&doc, 5), vec!["i", "ii", "A-1", "A-2", "A-3"]);
+        assert_eq!(select_pages(&doc, "A-2"), Ok(vec![4]));
+        assert_eq!(select_pages(&doc, "ii,A-1-A-3"), Ok(vec![2, 3, 4, 5]));
+        assert_eq!(select_pages(&doc, "1"), Ok(vec![1]));
+        assert!(page_labels(&document(2, None), 2).is_empty());
+    }
+
+    #[test]
+    fn roman_and_letter_numbering() {
+        assert_eq!(roman(1994), "MCMXCIV");
+        assert_eq!(letters(1), "A");
+        assert_eq!(letters(28), "BB");
+        assert_eq!(roman(4000), "4000");
+        assert_eq!(letters(i64::MAX), i64::MAX.to_string());
+    }
+
+    #[test]
+    fn out_of_range_label_starts() {
+        let doc = document(
+            2,
+            Some(vec![
+                i64::MIN.into(),
+                Object::Dictionary(dictionary! {"S" => "D", "P" => Object::string_literal("P")}),
+                1.into(),
+                Object::Dictionary(dictionary! {"S" => "R", "St" => i64::MAX}),
+            ]),
+        );
+        assert_eq!(page_labels(&doc, 2), vec!["P", "9223372036854775807"]);
+    }
+}
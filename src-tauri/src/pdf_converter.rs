@@ -1,12 +1,192 @@
-use lopdf::{content::Content, Document, Object};
+use crate::arc_fitting;
+use crate::blocks;
+use crate::clipping::{self, ClipRegion};
+use crate::color::{self, ColorSpace, Rgb};
+use crate::optional_content::{ContentLayer, OptionalContent};
+use crate::font::Font;
+use crate::glyph_outlines;
+use crate::dxf_patch::Patches;
+use crate::hatch::{self, HatchLoop};
+use crate::images::{self, DecodedImage};
+use crate::inline_images;
+use crate::layouts;
+use crate::names;
+use crate::page_selection;
+use crate::pdf_objects::{as_f64, resolve, resolve_dict};
+use crate::text::{TextEntity, TextObject, TextState};
+use lopdf::{content::Content, Document, Object, ObjectId, Stream};
+use serde::Deserialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::io;
+use std::ops::Range;
 use std::path::Path;
+use std::rc::Rc;
 use dxf::Drawing;
-use dxf::entities::{Entity, Line};
+use dxf::entities::{Arc, Circle, Entity, Image, Insert, Line, LwPolyline, MText, ModelPoint, Polyline, Spline, Text, Vertex};
+use dxf::objects::{Dictionary, ImageDefinition, ObjectType};
+use dxf::tables::{Layer, LineType};
+use dxf::LwPolylineVertex;
+use dxf::enums::{AcadVersion, AttachmentPoint};
+
+// --- Options de Conversion ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum DxfVersion {
+    R12,
+    R2000,
+    R2004,
+    R2007,
+    R2010,
+    R2013,
+    R2018,
+}
+
+impl DxfVersion {
+    pub fn acad_version(&self) -> AcadVersion {
+        match self {
+            DxfVersion::R12 => AcadVersion::R12,
+            DxfVersion::R2000 => AcadVersion::R2000,
+            DxfVersion::R2004 => AcadVersion::R2004,
+            DxfVersion::R2007 => AcadVersion::R2007,
+            DxfVersion::R2010 => AcadVersion::R2010,
+            DxfVersion::R2013 => AcadVersion::R2013,
+            DxfVersion::R2018 => AcadVersion::R2018,
+        }
+    }
+
+    // Les entités SPLINE n'existent qu'à partir de R13
+    pub fn supports_splines(&self) -> bool {
+        *self != DxfVersion::R12
+    }
+
+    // Couleur vraie (groupe 420) à partir de R2004
+    pub fn supports_true_color(&self) -> bool {
+        !matches!(self, DxfVersion::R12 | DxfVersion::R2000)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CurveMode {
+    // Les courbes de Bézier sont découpées en segments de droite
+    Flatten,
+    // Les courbes de Bézier sont conservées en SPLINE (repli sur Flatten en R12)
+    Spline,
+}
+
+// Représentation de l'épaisseur de trait en R12, qui ne connaît pas les épaisseurs de ligne (groupe 370)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum R12LineWidth {
+    None,
+    // Largeur constante de polyligne, en unités du dessin
+    Polyline,
+    // Un calque par épaisseur normalisée (LW_0.35...)
+    Layers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextMode {
+    // Entités TEXT / MTEXT, dépendantes des polices installées
+    Text,
+    // Contours des glyphes des polices incorporées, en polylignes fermées (gravure laser)
+    Outlines,
+}
+
+// Cadre de page retenu comme limite d'extraction ; le contenu qui en déborde (fonds perdus, repères d'impression) est découpé
+// Les variants reprennent les clés PDF, qui sont aussi les valeurs attendues côté interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[allow(clippy::enum_variant_names)]
+pub enum PageBox {
+    MediaBox,
+    // Zone visible dans les visionneuses (par défaut la MediaBox)
+    CropBox,
+    // Format fini après massicotage (par défaut la CropBox)
+    TrimBox,
+    // Contenu utile défini par l'auteur (par défaut la CropBox)
+    ArtBox,
+}
+
+impl PageBox {
+    fn key(&self) -> &'static [u8] {
+        match self {
+            PageBox::MediaBox => b"MediaBox",
+            PageBox::CropBox => b"CropBox",
+            PageBox::TrimBox => b"TrimBox",
+            PageBox::ArtBox => b"ArtBox",
+        }
+    }
+}
+
+// Disposition des pages dans le dessin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageLayout {
+    // Pages côte à côte le long de X
+    Strip,
+    // Grille de `gridColumns` colonnes, les lignes se succédant vers le bas
+    Grid,
+    // Pages empilées vers le bas
+    Stack,
+    // Pages superposées à l'origine
+    Overlay,
+    // Un fichier DXF par page (`<nom>_p<page>.dxf`), chaque page à l'origine
+    Separate,
+    // Une présentation (espace papier) par page, nommée d'après son libellé, chaque page à l'origine ; R2000+
+    Layouts,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConversionOptions {
+    pub dxf_version: DxfVersion,
+    pub curve_mode: CurveMode,
+    // Écart maximal toléré entre une courbe et sa discrétisation, en unités de sortie (mm après échelle)
+    pub tolerance: f64,
+    // Reconnaissance des cercles et arcs approchés par des Béziers
+    pub detect_arcs: bool,
+    // Chaînage des segments de chaque sous-chemin en POLYLINE (R12) / LWPOLYLINE (R2000+)
+    pub polylines: bool,
+    #[serde(rename = "lineWidthR12")]
+    pub line_width_r12: R12LineWidth,
+    pub text_mode: TextMode,
+    pub page_box: PageBox,
+    pub page_layout: PageLayout,
+    pub grid_columns: usize,
+    // Espace entre deux pages voisines, en points PDF (1/72")
+    pub page_margin: f64,
+    // Pages à convertir (« 1,3-5,last », libellés de page acceptés) ; toutes les pages si absent
+    pub pages: Option<String>,
+    // Formulaires (Form XObjects) appelés plusieurs fois définis une seule fois en BLOCK, chaque `Do` devenant une INSERT
+    pub form_blocks: bool,
+}
+
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        ConversionOptions {
+            dxf_version: DxfVersion::R12,
+            curve_mode: CurveMode::Flatten,
+            tolerance: 0.1,
+            detect_arcs: true,
+            polylines: true,
+            line_width_r12: R12LineWidth::None,
+            text_mode: TextMode::Text,
+            page_box: PageBox::CropBox,
+            page_layout: PageLayout::Strip,
+            grid_columns: 4,
+            page_margin: 200.0,
+            pages: None,
+            form_blocks: false,
+        }
+    }
+}
 
 // --- Structures de Données ---
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -42,6 +222,11 @@ impl Transform {
         }
     }
 
+    // Facteur d'échelle moyen de la transformation (racine du déterminant)
+    pub fn scale(&self) -> f64 {
+        (self.a * self.d - self.b * self.c).abs().sqrt()
+    }
+
     pub fn apply(&self, p: Point) -> Point {
         Point {
             x: self.a * p.x + self.c * p.y + self.e,
@@ -50,70 +235,674 @@ impl Transform {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LineEntity {
     pub start: Point,
     pub end: Point,
 }
 
-// --- Fonctions Utilitaires ---
+#[derive(Debug, Clone)]
+pub struct BezierEntity {
+    pub p0: Point,
+    pub p1: Point,
+    pub p2: Point,
+    pub p3: Point,
+}
 
-fn as_f64(obj: &Object) -> f64 {
-    match obj {
-        Object::Integer(i) => *i as f64,
-        Object::Real(f) => *f as f64,
-        _ => 0.0,
+impl BezierEntity {
+    pub fn point_at(&self, t: f64) -> Point {
+        let u = 1.0 - t;
+        let (b0, b1, b2, b3) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+        Point {
+            x: b0 * self.p0.x + b1 * self.p1.x + b2 * self.p2.x + b3 * self.p3.x,
+            y: b0 * self.p0.y + b1 * self.p1.y + b2 * self.p2.y + b3 * self.p3.y,
+        }
     }
 }
 
-fn bezier_to_lines(p0: Point, p1: Point, p2: Point, p3: Point, segments: usize) -> Vec<LineEntity> {
-    let mut lines = Vec::with_capacity(segments);
-    let mut prev_point = p0;
+// Angles en radians ; le balayage est signé (positif dans le sens trigonométrique)
+// afin de conserver le sens de parcours du tracé
+#[derive(Debug, Clone)]
+pub struct ArcEntity {
+    pub center: Point,
+    pub radius: f64,
+    pub start_angle: f64,
+    pub sweep: f64,
+}
 
-    for i in 1..=segments {
-        let t = i as f64 / segments as f64;
-        let t2 = t * t;
-        let t3 = t2 * t;
-        let u = 1.0 - t;
-        let u2 = u * u;
-        let u3 = u2 * u;
+impl ArcEntity {
+    pub fn point_at_angle(&self, angle: f64) -> Point {
+        Point {
+            x: self.center.x + self.radius * angle.cos(),
+            y: self.center.y + self.radius * angle.sin(),
+        }
+    }
 
-        let x = u3 * p0.x + 3.0 * u2 * t * p1.x + 3.0 * u * t2 * p2.x + t3 * p3.x;
-        let y = u3 * p0.y + 3.0 * u2 * t * p1.y + 3.0 * u * t2 * p2.y + t3 * p3.y;
+    pub fn start(&self) -> Point {
+        self.point_at_angle(self.start_angle)
+    }
 
-        let current_point = Point { x, y };
-        lines.push(LineEntity {
-            start: prev_point,
-            end: current_point,
-        });
-        prev_point = current_point;
+    pub fn end(&self) -> Point {
+        self.point_at_angle(self.start_angle + self.sweep)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CircleEntity {
+    pub center: Point,
+    pub radius: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum PathSegment {
+    Line(LineEntity),
+    Bezier(BezierEntity),
+    Arc(ArcEntity),
+    Circle(CircleEntity),
+}
+
+impl PathSegment {
+    pub fn start(&self) -> Point {
+        match self {
+            PathSegment::Line(line) => line.start,
+            PathSegment::Bezier(bezier) => bezier.p0,
+            PathSegment::Arc(arc) => arc.start(),
+            PathSegment::Circle(circle) => Point { x: circle.center.x + circle.radius, y: circle.center.y },
+        }
+    }
+
+    pub fn end(&self) -> Point {
+        match self {
+            PathSegment::Line(line) => line.end,
+            PathSegment::Bezier(bezier) => bezier.p3,
+            PathSegment::Arc(arc) => arc.end(),
+            PathSegment::Circle(_) => self.start(),
+        }
+    }
+}
+
+// Sous-chemin PDF : suite de segments démarrée par `m` (ou `re`), fermée par `h` ou `re`
+#[derive(Debug, Clone, Default)]
+pub struct SubPath {
+    pub segments: Vec<PathSegment>,
+    pub closed: bool,
+}
+
+impl SubPath {
+    // Ajoute si nécessaire le segment de fermeture vers le point de départ
+    pub fn close(&mut self) {
+        if let (Some(first), Some(last)) = (self.segments.first(), self.segments.last()) {
+            let (start, end) = (first.start(), last.end());
+            if start != end {
+                self.segments.push(PathSegment::Line(LineEntity { start: end, end: start }));
+            }
+        }
+        self.closed = true;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+// Motif de tirets (`d`) : longueurs alternées trait / espace et décalage de départ
+#[derive(Debug, Clone, PartialEq)]
+pub struct DashPattern {
+    pub array: Vec<f64>,
+    pub phase: f64,
+}
+
+impl DashPattern {
+    // Un tableau vide (ou de longueur totale nulle) désigne un trait continu
+    fn from_operands(array: &Object, phase: &Object) -> Option<Self> {
+        let array: Vec<f64> = array.as_array().ok()?.iter().map(|v| as_f64(v).abs()).collect();
+        if array.iter().sum::<f64>() <= 0.0 {
+            return None;
+        }
+        Some(DashPattern { array, phase: as_f64(phase) })
+    }
+
+    fn scaled(&self, factor: f64) -> Self {
+        DashPattern {
+            array: self.array.iter().map(|v| v * factor).collect(),
+            phase: self.phase * factor,
+        }
+    }
+}
+
+// Chemin PDF effectivement peint (`S`, `f`, `B`...), avec le mode de peinture utilisé
+#[derive(Debug, Clone)]
+pub struct PathEntity {
+    pub subpaths: Vec<SubPath>,
+    // Contours fermés de la surface remplie visible, bordure des hachures ; vide sans remplissage
+    pub fill_area: Vec<SubPath>,
+    pub stroke: bool,
+    pub fill: Option<FillRule>,
+    pub stroke_color: Rgb,
+    pub fill_color: Rgb,
+    // Épaisseur du trait en points, transformée par la CTM
+    pub line_width: f64,
+    // Motif de tirets en points, transformé par la CTM ; `None` pour un trait continu
+    pub dash: Option<DashPattern>,
+    // Calque PDF (OCG) englobant, `None` hors contenu optionnel
+    pub layer: Option<ContentLayer>,
+}
+
+// Origine d'une image : XObject des ressources, ou image en ligne (rang dans `PdfContent::inline_images`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageSource {
+    XObject(ObjectId),
+    Inline(usize),
+}
+
+// Image en ligne (`BI` ... `EI`), conservée sous forme de flux aux clés développées
+#[derive(Debug, Clone)]
+pub struct InlineImage {
+    pub page: u32,
+    pub stream: Stream,
+}
+
+// Image dessinée par `Do` ou en ligne : le carré unité de l'espace image est placé par la CTM
+#[derive(Debug, Clone)]
+pub struct ImagePlacement {
+    pub source: ImageSource,
+    pub matrix: Transform,
+    // Dimensions en pixels (/Width, /Height)
+    pub width: u32,
+    pub height: u32,
+    pub layer: Option<ContentLayer>,
+}
+
+// Formulaire répété, défini une fois : son contenu est exprimé dans l'espace de l'appelant, à la CTM près
+#[derive(Debug)]
+pub struct FormBlock {
+    pub form: ObjectId,
+    pub name: String,
+    pub content: PdfContent,
+    // État de l'appelant figé dans la définition
+    inherited: InheritedState,
+}
+
+// Attributs qu'un formulaire hérite de son appelant (couleurs, épaisseur et tirets) et échelle de la CTM, qui fixe
+// l'épaisseur des traits : un appel ne réutilise le bloc que s'ils sont identiques à ceux du premier appel
+#[derive(Debug)]
+struct InheritedState {
+    stroke_color: Rgb,
+    fill_color: Rgb,
+    line_width: f64,
+    dash: Option<DashPattern>,
+    scale: f64,
+}
+
+impl InheritedState {
+    fn matches(&self, other: &InheritedState) -> bool {
+        self.stroke_color == other.stroke_color
+            && self.fill_color == other.fill_color
+            && self.line_width == other.line_width
+            && self.dash == other.dash
+            && (self.scale - other.scale).abs() <= 1e-9 * self.scale
+    }
+}
+
+// Appel `Do` d'un formulaire défini en bloc (rang dans `PdfContent::blocks`), placé par la CTM
+#[derive(Debug, Clone)]
+pub struct FormInsert {
+    pub block: usize,
+    pub matrix: Transform,
+    pub layer: Option<ContentLayer>,
+}
+
+// Page extraite vue comme une feuille : libellé, format et fin de son contenu dans `paths`, `texts`, `images` et `inserts`
+#[derive(Debug, Clone)]
+pub struct Sheet {
+    pub label: String,
+    // Largeur et hauteur en points, rotation et unité utilisateur appliquées
+    pub size: (f64, f64),
+    pub paths_end: usize,
+    pub texts_end: usize,
+    pub images_end: usize,
+    pub inserts_end: usize,
+}
+
+// Partie du contenu écrite d'un seul tenant : une feuille, tout le dessin ou la définition d'un bloc
+struct ContentRanges {
+    images: Range<usize>,
+    paths: Range<usize>,
+    texts: Range<usize>,
+    inserts: Range<usize>,
+}
+
+// Contenu extrait du PDF : chemins peints, textes et emplacements des images
+#[derive(Debug, Default)]
+pub struct PdfContent {
+    pub paths: Vec<PathEntity>,
+    pub texts: Vec<TextEntity>,
+    pub images: Vec<ImagePlacement>,
+    pub inline_images: Vec<InlineImage>,
+    // Numéros des pages extraites, dans l'ordre
+    pub pages: Vec<u32>,
+    // Feuille de chaque page extraite, dans le même ordre
+    pub sheets: Vec<Sheet>,
+    // Formulaires définis en blocs (`formBlocks`) et leurs insertions
+    pub blocks: Vec<FormBlock>,
+    pub inserts: Vec<FormInsert>,
+}
+
+impl PdfContent {
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.texts.is_empty() && self.images.is_empty() && self.inserts.is_empty()
+    }
+
+    fn all_ranges(&self) -> ContentRanges {
+        ContentRanges {
+            images: 0..self.images.len(),
+            paths: 0..self.paths.len(),
+            texts: 0..self.texts.len(),
+            inserts: 0..self.inserts.len(),
+        }
+    }
+
+    // Plages d'images, de chemins, de textes et d'insertions de chaque feuille
+    fn sheet_ranges(&self) -> Vec<ContentRanges> {
+        let mut start = (0, 0, 0, 0);
+        self.sheets
+            .iter()
+            .map(|sheet| {
+                let ranges = ContentRanges {
+                    images: start.0..sheet.images_end,
+                    paths: start.1..sheet.paths_end,
+                    texts: start.2..sheet.texts_end,
+                    inserts: start.3..sheet.inserts_end,
+                };
+                start = (sheet.images_end, sheet.paths_end, sheet.texts_end, sheet.inserts_end);
+                ranges
+            })
+            .collect()
+    }
+}
+
+fn flush_subpath(current: &mut SubPath, current_path: &mut Vec<SubPath>) {
+    if !current.segments.is_empty() {
+        current_path.push(std::mem::take(current));
+    }
+    current.closed = false;
+}
+
+// Opérateurs de peinture : (fermeture du sous-chemin courant, tracé du contour, règle de remplissage)
+fn painting_operator(operator: &str) -> Option<(bool, bool, Option<FillRule>)> {
+    match operator {
+        "S" => Some((false, true, None)),
+        "s" => Some((true, true, None)),
+        "f" | "F" => Some((false, false, Some(FillRule::NonZero))),
+        "f*" => Some((false, false, Some(FillRule::EvenOdd))),
+        "B" => Some((false, true, Some(FillRule::NonZero))),
+        "B*" => Some((false, true, Some(FillRule::EvenOdd))),
+        "b" => Some((true, true, Some(FillRule::NonZero))),
+        "b*" => Some((true, true, Some(FillRule::EvenOdd))),
+        _ => None,
+    }
+}
+
+// --- Fonctions Utilitaires ---
+
+fn midpoint(a: Point, b: Point) -> Point {
+    Point { x: (a.x + b.x) / 2.0, y: (a.y + b.y) / 2.0 }
+}
+
+// Distance d'un point à la droite (ou au point) définie par a et b
+fn distance_to_chord(p: Point, a: Point, b: Point) -> f64 {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let len = (dx * dx + dy * dy).sqrt();
+    if len < 1e-12 {
+        return ((p.x - a.x).powi(2) + (p.y - a.y).powi(2)).sqrt();
+    }
+    ((p.x - a.x) * dy - (p.y - a.y) * dx).abs() / len
+}
+
+const MAX_FLATTEN_DEPTH: u32 = 16;
+
+// Subdivision récursive (de Casteljau) jusqu'à ce que l'écart à la corde soit inférieur à la tolérance
+fn flatten_bezier_rec(p0: Point, p1: Point, p2: Point, p3: Point, tolerance: f64, depth: u32, out: &mut Vec<LineEntity>) {
+    // L'écart maximal de la courbe à la corde est borné par les 3/4 de l'écart des points de contrôle
+    let deviation = 0.75 * distance_to_chord(p1, p0, p3).max(distance_to_chord(p2, p0, p3));
+    if deviation <= tolerance || depth >= MAX_FLATTEN_DEPTH {
+        out.push(LineEntity { start: p0, end: p3 });
+        return;
     }
 
+    let p01 = midpoint(p0, p1);
+    let p12 = midpoint(p1, p2);
+    let p23 = midpoint(p2, p3);
+    let p012 = midpoint(p01, p12);
+    let p123 = midpoint(p12, p23);
+    let mid = midpoint(p012, p123);
+
+    flatten_bezier_rec(p0, p01, p012, mid, tolerance, depth + 1, out);
+    flatten_bezier_rec(mid, p123, p23, p3, tolerance, depth + 1, out);
+}
+
+pub fn bezier_to_lines(bezier: &BezierEntity, tolerance: f64) -> Vec<LineEntity> {
+    let mut lines = Vec::new();
+    flatten_bezier_rec(bezier.p0, bezier.p1, bezier.p2, bezier.p3, tolerance, 0, &mut lines);
     lines
 }
 
+// État graphique sauvegardé par `q` et restauré par `Q`
+#[derive(Debug, Clone)]
+struct GraphicsState {
+    ctm: Transform,
+    // Zones de découpage actives : un point n'est visible que s'il est dans toutes
+    clip: Vec<Rc<ClipRegion>>,
+    stroke_space: ColorSpace,
+    stroke_color: Rgb,
+    fill_space: ColorSpace,
+    fill_color: Rgb,
+    // Épaisseur en unités utilisateur (`w`), convertie à la peinture avec la CTM courante
+    line_width: f64,
+    // Motif de tirets en unités utilisateur (`d`)
+    dash: Option<DashPattern>,
+    text: TextState,
+}
+
+impl GraphicsState {
+    fn new(ctm: Transform) -> Self {
+        GraphicsState {
+            ctm,
+            clip: Vec::new(),
+            stroke_space: ColorSpace::Gray,
+            stroke_color: Rgb::BLACK,
+            fill_space: ColorSpace::Gray,
+            fill_color: Rgb::BLACK,
+            line_width: 1.0,
+            dash: None,
+            text: TextState::default(),
+        }
+    }
+
+    // Paramètres d'un dictionnaire ExtGState (`gs`)
+    fn apply_ext_gstate(&mut self, doc: &Document, resources: Option<&lopdf::Dictionary>, name: &[u8]) {
+        let ext_gstate = resources
+            .and_then(|res| res.get(b"ExtGState").ok())
+            .and_then(|o| doc.dereference(o).ok())
+            .and_then(|(_, o)| o.as_dict().ok())
+            .and_then(|dict| dict.get(name).ok())
+            .and_then(|o| doc.dereference(o).ok())
+            .and_then(|(_, o)| o.as_dict().ok());
+        if let Some(dict) = ext_gstate {
+            if let Ok(width) = dict.get(b"LW") {
+                self.line_width = as_f64(width);
+            }
+            // D : [[tableau] décalage]
+            if let Ok(Object::Array(dash)) = dict.get(b"D").and_then(|o| doc.dereference(o)).map(|(_, o)| o) {
+                if dash.len() == 2 {
+                    self.dash = DashPattern::from_operands(&dash[0], &dash[1]);
+                }
+            }
+        }
+    }
+
+    // Chemin peint avec l'état courant, découpé par les zones de découpage actives
+    fn painted_path(&self, subpaths: Vec<SubPath>, stroke: bool, fill: Option<FillRule>, layer: Option<ContentLayer>) -> Option<PathEntity> {
+        let clips: Vec<&ClipRegion> = self.clip.iter().map(|c| c.as_ref()).collect();
+        let mut visible: Vec<SubPath> = Vec::new();
+        let mut fill_area: Vec<SubPath> = Vec::new();
+        for subpath in subpaths {
+            let pieces = clipping::clip_subpath(subpath.clone(), &clips);
+            // Un contour resté fermé est entièrement visible
+            let fully_visible = matches!(pieces.as_slice(), [piece] if piece.closed);
+            if fill.is_none() || fully_visible {
+                fill_area.extend(pieces.iter().filter(|piece| fill.is_some() && piece.closed).cloned());
+                visible.extend(pieces);
+                continue;
+            }
+            match clipping::clip_fill(&subpath, &clips) {
+                Some(area) => {
+                    // Sans trait, le contour dessiné est celui de la surface découpée
+                    if !stroke {
+                        visible.extend(area.iter().cloned());
+                    } else {
+                        visible.extend(pieces);
+                    }
+                    fill_area.extend(area);
+                }
+                // Découpage non convexe : seuls les morceaux du contour sont conservés
+                None => visible.extend(pieces),
+            }
+        }
+        if visible.is_empty() && fill_area.is_empty() {
+            return None;
+        }
+        Some(PathEntity {
+            subpaths: visible,
+            fill_area,
+            stroke,
+            fill,
+            stroke_color: self.stroke_color,
+            fill_color: self.fill_color,
+            line_width: self.line_width * self.ctm.scale(),
+            dash: self.dash.as_ref().map(|dash| dash.scaled(self.ctm.scale())),
+            layer,
+        })
+    }
+
+    fn set_color(&mut self, stroking: bool, space: Option<ColorSpace>, components: &[f64]) {
+        if let Some(space) = space {
+            if stroking {
+                self.stroke_space = space;
+            } else {
+                self.fill_space = space;
+            }
+        }
+        let space = if stroking { &self.stroke_space } else { &self.fill_space };
+        // Les motifs (Pattern) n'ont pas de couleur unique : on conserve la précédente
+        if let Some(color) = space.to_rgb(components) {
+            if stroking {
+                self.stroke_color = color;
+            } else {
+                self.fill_color = color;
+            }
+        }
+    }
+}
+
+// Données communes à tous les flux de contenu du document
+struct ParseContext<'a> {
+    doc: &'a Document,
+    optional_content: OptionalContent,
+    // Texte converti en contours de glyphes plutôt qu'en entités TEXT / MTEXT
+    text_outlines: bool,
+    // Numéro de la page en cours, qui nomme les fichiers des images en ligne
+    page: u32,
+    // Formulaires à définir en blocs : appelés plusieurs fois (`formBlocks`)
+    form_blocks: HashSet<ObjectId>,
+}
+
+// `visiting` retient les formulaires en cours de lecture, comme pour `count_form_uses`
 fn parse_content_stream(
-    doc: &Document,
+    ctx: &ParseContext,
     resources: Option<&lopdf::Dictionary>,
     content_data: &[u8],
-    base_ctm: Transform,
-    all_lines: &mut Vec<LineEntity>,
+    base_state: GraphicsState,
+    base_layer: Option<ContentLayer>,
+    visiting: &mut Vec<ObjectId>,
+    extracted: &mut PdfContent,
 ) {
-    if let Ok(content) = Content::decode(content_data) {
-        let mut ctm_stack: Vec<Transform> = Vec::new();
-        let mut current_ctm = base_ctm;
+    let doc = ctx.doc;
+    let optional_content = &ctx.optional_content;
+    let (content_data, inline_streams) = inline_images::split_inline_images(content_data);
+    if let Ok(content) = Content::decode(&content_data) {
+        let mut state_stack: Vec<GraphicsState> = Vec::new();
+        let mut state = base_state;
+        // Séquences de contenu marqué ouvertes (`BMC` / `BDC` ... `EMC`) ; seules celles en `/OC` désignent un calque
+        let mut marked_content: Vec<Option<ContentLayer>> = Vec::new();
+        let mut text_object = TextObject::default();
+        let mut fonts: HashMap<Vec<u8>, Rc<Font>> = HashMap::new();
+        // Règle de découpage demandée par `W` / `W*`, appliquée à la fin du chemin courant
+        let mut pending_clip: Option<FillRule> = None;
 
         let mut current_point = Point { x: 0.0, y: 0.0 };
         let mut subpath_start = Point { x: 0.0, y: 0.0 };
+        // Le chemin n'est conservé que lorsqu'un opérateur de peinture le trace ou le remplit
+        let mut current_path: Vec<SubPath> = Vec::new();
+        let mut current_subpath = SubPath::default();
 
         for op in &content.operations {
             match op.operator.as_str() {
-                "q" => ctm_stack.push(current_ctm),
+                "q" => state_stack.push(state.clone()),
                 "Q" => {
-                    if let Some(matrix) = ctm_stack.pop() {
-                        current_ctm = matrix;
+                    if let Some(saved) = state_stack.pop() {
+                        state = saved;
+                    }
+                }
+                // Couleurs : les opérateurs en majuscules concernent le contour, en minuscules le remplissage
+                "G" | "g" | "RG" | "rg" | "K" | "k" => {
+                    let space = match op.operator.as_str() {
+                        "G" | "g" => ColorSpace::Gray,
+                        "RG" | "rg" => ColorSpace::Rgb,
+                        _ => ColorSpace::Cmyk,
+                    };
+                    let components: Vec<f64> = op.operands.iter().map(as_f64).collect();
+                    state.set_color(op.operator.starts_with(char::is_uppercase), Some(space), &components);
+                }
+                "CS" | "cs" => {
+                    if let Some(Object::Name(name)) = op.operands.first() {
+                        let space = ColorSpace::from_name(doc, resources, name);
+                        let components = space.initial_components();
+                        state.set_color(op.operator == "CS", Some(space), &components);
+                    }
+                }
+                "SC" | "SCN" | "sc" | "scn" => {
+                    // Le nom de motif éventuel de SCN / scn est ignoré
+                    let components: Vec<f64> = op
+                        .operands
+                        .iter()
+                        .filter(|o| matches!(o, Object::Integer(_) | Object::Real(_)))
+                        .map(as_f64)
+                        .collect();
+                    state.set_color(op.operator.starts_with(char::is_uppercase), None, &components);
+                }
+                "w" => {
+                    if let Some(width) = op.operands.first() {
+                        state.line_width = as_f64(width);
+                    }
+                }
+                "d" => {
+                    if op.operands.len() == 2 {
+                        state.dash = DashPattern::from_operands(&op.operands[0], &op.operands[1]);
+                    }
+                }
+                "gs" => {
+                    if let Some(Object::Name(name)) = op.operands.first() {
+                        state.apply_ext_gstate(doc, resources, name);
                     }
                 }
+                "BMC" => marked_content.push(None),
+                "BDC" => {
+                    let layer = match (op.operands.first(), op.operands.get(1)) {
+                        (Some(Object::Name(tag)), Some(Object::Name(name))) if tag == b"OC" => {
+                            optional_content.layer_for_property(doc, resources, name)
+                        }
+                        _ => None,
+                    };
+                    marked_content.push(layer);
+                }
+                "EMC" => {
+                    marked_content.pop();
+                }
+                // Texte : paramètres de l'état graphique, puis positionnement et affichage dans `BT` ... `ET`
+                "BT" => text_object = TextObject::default(),
+                "Tf" => {
+                    if let (Some(Object::Name(name)), Some(size)) = (op.operands.first(), op.operands.get(1)) {
+                        if !fonts.contains_key(name) {
+                            if let Some(font) = Font::load(doc, resources, name) {
+                                fonts.insert(name.clone(), Rc::new(font));
+                            }
+                        }
+                        state.text.font = fonts.get(name).cloned();
+                        state.text.size = as_f64(size);
+                    }
+                }
+                "Tc" | "Tw" | "Tz" | "TL" | "Ts" | "Tr" => {
+                    if let Some(value) = op.operands.first().map(as_f64) {
+                        match op.operator.as_str() {
+                            "Tc" => state.text.char_spacing = value,
+                            "Tw" => state.text.word_spacing = value,
+                            "Tz" => state.text.horizontal_scaling = value / 100.0,
+                            "TL" => state.text.leading = value,
+                            "Ts" => state.text.rise = value,
+                            _ => state.text.render_mode = value as i64,
+                        }
+                    }
+                }
+                "Td" | "TD" => {
+                    if op.operands.len() == 2 {
+                        let (tx, ty) = (as_f64(&op.operands[0]), as_f64(&op.operands[1]));
+                        if op.operator == "TD" {
+                            state.text.leading = -ty;
+                        }
+                        text_object.move_line(tx, ty);
+                    }
+                }
+                "Tm" => {
+                    if op.operands.len() == 6 {
+                        text_object.set_matrix(Transform {
+                            a: as_f64(&op.operands[0]),
+                            b: as_f64(&op.operands[1]),
+                            c: as_f64(&op.operands[2]),
+                            d: as_f64(&op.operands[3]),
+                            e: as_f64(&op.operands[4]),
+                            f: as_f64(&op.operands[5]),
+                        });
+                    }
+                }
+                "T*" => text_object.move_line(0.0, -state.text.leading),
+                "Tj" | "TJ" | "'" | "\"" => {
+                    let shown = match op.operator.as_str() {
+                        "TJ" => op.operands.first().and_then(|o| o.as_array().ok()).map(|a| a.as_slice()),
+                        "\"" => {
+                            state.text.word_spacing = op.operands.first().map(as_f64).unwrap_or(0.0);
+                            state.text.char_spacing = op.operands.get(1).map(as_f64).unwrap_or(0.0);
+                            text_object.move_line(0.0, -state.text.leading);
+                            op.operands.get(2..)
+                        }
+                        "'" => {
+                            text_object.move_line(0.0, -state.text.leading);
+                            Some(op.operands.as_slice())
+                        }
+                        _ => Some(op.operands.as_slice()),
+                    };
+                    let shown = shown.and_then(|items| text_object.show(&state.text, &state.ctm, items));
+                    let font = state.text.font.clone();
+                    // Texte invisible (mode 3) ou de découpage seul (mode 7) : rien n'est exporté
+                    if let (Some(shown), Some(font), true) = (shown, font, state.text.is_visible()) {
+                        let layer = marked_content.iter().rev().find_map(Clone::clone).or_else(|| base_layer.clone());
+                        if ctx.text_outlines && font.has_outlines() {
+                            // Chaque glyphe est dessiné par son contour, transformé par sa matrice de rendu
+                            let mut subpaths = Vec::new();
+                            for (glyph, matrix) in &shown.glyphs {
+                                if let Some(outline) = font.outline(glyph) {
+                                    subpaths.extend(glyph_outlines::transform_subpaths(&outline, |p| matrix.apply(p)));
+                                }
+                            }
+                            let (stroke, fill) = state.text.outline_painting();
+                            if !subpaths.is_empty() {
+                                extracted.paths.extend(state.painted_path(subpaths, stroke, fill.then_some(FillRule::NonZero), layer));
+                            }
+                        } else if !shown.text.trim().is_empty() {
+                            let color = if state.text.uses_stroke_color() { state.stroke_color } else { state.fill_color };
+                            let entity = TextEntity::from_render_matrix(shown.text, &shown.matrix, color, layer);
+                            // Un texte est conservé si son point d'insertion est visible
+                            if state.clip.iter().all(|clip| clip.contains(entity.position)) {
+                                extracted.texts.push(entity);
+                            }
+                        }
+                    }
+                }
+                "W" => pending_clip = Some(FillRule::NonZero),
+                "W*" => pending_clip = Some(FillRule::EvenOdd),
                 "cm" => {
                     if op.operands.len() == 6 {
                         let new_matrix = Transform {
@@ -124,59 +913,59 @@ fn parse_content_stream(
                             e: as_f64(&op.operands[4]),
                             f: as_f64(&op.operands[5]),
                         };
-                        current_ctm = current_ctm.multiply(&new_matrix);
+                        // La nouvelle matrice s'applique avant la CTM courante
+                        state.ctm = new_matrix.multiply(&state.ctm);
                     }
                 }
                 "m" => {
                     if op.operands.len() == 2 {
-                        let p = current_ctm.apply(Point {
+                        let p = state.ctm.apply(Point {
                             x: as_f64(&op.operands[0]),
                             y: as_f64(&op.operands[1]),
                         });
+                        flush_subpath(&mut current_subpath, &mut current_path);
                         current_point = p;
                         subpath_start = p;
                     }
                 }
                 "l" => {
                     if op.operands.len() == 2 {
-                        let p = current_ctm.apply(Point {
+                        let p = state.ctm.apply(Point {
                             x: as_f64(&op.operands[0]),
                             y: as_f64(&op.operands[1]),
                         });
-                        all_lines.push(LineEntity { start: current_point, end: p });
+                        current_subpath.segments.push(PathSegment::Line(LineEntity { start: current_point, end: p }));
                         current_point = p;
                     }
                 }
                 "c" => {
                     if op.operands.len() == 6 {
-                        let p1 = current_ctm.apply(Point { x: as_f64(&op.operands[0]), y: as_f64(&op.operands[1]) });
-                        let p2 = current_ctm.apply(Point { x: as_f64(&op.operands[2]), y: as_f64(&op.operands[3]) });
-                        let p3 = current_ctm.apply(Point { x: as_f64(&op.operands[4]), y: as_f64(&op.operands[5]) });
-                        let curves = bezier_to_lines(current_point, p1, p2, p3, 10);
-                        all_lines.extend(curves);
+                        let p1 = state.ctm.apply(Point { x: as_f64(&op.operands[0]), y: as_f64(&op.operands[1]) });
+                        let p2 = state.ctm.apply(Point { x: as_f64(&op.operands[2]), y: as_f64(&op.operands[3]) });
+                        let p3 = state.ctm.apply(Point { x: as_f64(&op.operands[4]), y: as_f64(&op.operands[5]) });
+                        current_subpath.segments.push(PathSegment::Bezier(BezierEntity { p0: current_point, p1, p2, p3 }));
                         current_point = p3;
                     }
                 }
                 "v" => {
                     if op.operands.len() == 4 {
-                        let p2 = current_ctm.apply(Point { x: as_f64(&op.operands[0]), y: as_f64(&op.operands[1]) });
-                        let p3 = current_ctm.apply(Point { x: as_f64(&op.operands[2]), y: as_f64(&op.operands[3]) });
-                        let curves = bezier_to_lines(current_point, current_point, p2, p3, 10);
-                        all_lines.extend(curves);
+                        let p2 = state.ctm.apply(Point { x: as_f64(&op.operands[0]), y: as_f64(&op.operands[1]) });
+                        let p3 = state.ctm.apply(Point { x: as_f64(&op.operands[2]), y: as_f64(&op.operands[3]) });
+                        current_subpath.segments.push(PathSegment::Bezier(BezierEntity { p0: current_point, p1: current_point, p2, p3 }));
                         current_point = p3;
                     }
                 }
                 "y" => {
                     if op.operands.len() == 4 {
-                        let p1 = current_ctm.apply(Point { x: as_f64(&op.operands[0]), y: as_f64(&op.operands[1]) });
-                        let p3 = current_ctm.apply(Point { x: as_f64(&op.operands[2]), y: as_f64(&op.operands[3]) });
-                        let curves = bezier_to_lines(current_point, p1, p3, p3, 10);
-                        all_lines.extend(curves);
+                        let p1 = state.ctm.apply(Point { x: as_f64(&op.operands[0]), y: as_f64(&op.operands[1]) });
+                        let p3 = state.ctm.apply(Point { x: as_f64(&op.operands[2]), y: as_f64(&op.operands[3]) });
+                        current_subpath.segments.push(PathSegment::Bezier(BezierEntity { p0: current_point, p1, p2: p3, p3 }));
                         current_point = p3;
                     }
                 }
                 "h" => {
-                    all_lines.push(LineEntity { start: current_point, end: subpath_start });
+                    current_subpath.close();
+                    flush_subpath(&mut current_subpath, &mut current_path);
                     current_point = subpath_start;
                 }
                 "re" => {
@@ -186,50 +975,179 @@ fn parse_content_stream(
                         let w = as_f64(&op.operands[2]);
                         let h = as_f64(&op.operands[3]);
 
-                        let p_ll = current_ctm.apply(Point { x, y });
-                        let p_lr = current_ctm.apply(Point { x: x + w, y });
-                        let p_ur = current_ctm.apply(Point { x: x + w, y: y + h });
-                        let p_ul = current_ctm.apply(Point { x, y: y + h });
+                        let p_ll = state.ctm.apply(Point { x, y });
+                        let p_lr = state.ctm.apply(Point { x: x + w, y });
+                        let p_ur = state.ctm.apply(Point { x: x + w, y: y + h });
+                        let p_ul = state.ctm.apply(Point { x, y: y + h });
 
+                        flush_subpath(&mut current_subpath, &mut current_path);
                         subpath_start = p_ll;
-                        all_lines.push(LineEntity { start: p_ll, end: p_lr });
-                        all_lines.push(LineEntity { start: p_lr, end: p_ur });
-                        all_lines.push(LineEntity { start: p_ur, end: p_ul });
-                        all_lines.push(LineEntity { start: p_ul, end: p_ll });
+                        current_path.push(SubPath {
+                            segments: vec![
+                                PathSegment::Line(LineEntity { start: p_ll, end: p_lr }),
+                                PathSegment::Line(LineEntity { start: p_lr, end: p_ur }),
+                                PathSegment::Line(LineEntity { start: p_ur, end: p_ul }),
+                                PathSegment::Line(LineEntity { start: p_ul, end: p_ll }),
+                            ],
+                            closed: true,
+                        });
                         current_point = p_ll;
                     }
                 }
+                "n" => {
+                    // Chemin sans peinture (typiquement après `W` pour un découpage) : abandonné
+                    flush_subpath(&mut current_subpath, &mut current_path);
+                    if let Some(rule) = pending_clip.take() {
+                        state.clip.push(Rc::new(ClipRegion::from_subpaths(&current_path, rule)));
+                    }
+                    current_path.clear();
+                }
+
+                // Image en ligne, retirée du flux avant son décodage (`<rang> BI`)
+                "BI" => {
+                    let inline = op.operands.first().and_then(|o| o.as_i64().ok()).and_then(|index| inline_streams.get(index as usize));
+                    if let Some(inline) = inline {
+                        let mut stream = inline.clone();
+                        // Un espace de couleur nommé désigne une entrée des ressources /ColorSpace
+                        let named_space = stream.dict.get(b"ColorSpace").and_then(|o| o.as_name()).ok().and_then(|name| {
+                            resources
+                                .and_then(|res| res.get(b"ColorSpace").ok())
+                                .and_then(|cs| doc.dereference(cs).ok())
+                                .and_then(|(_, cs)| cs.as_dict().ok())
+                                .and_then(|cs| cs.get(name).ok())
+                                .cloned()
+                        });
+                        if let Some(space) = named_space {
+                            stream.dict.set("ColorSpace", space);
+                        }
+                        let dimension = |key: &[u8]| stream.dict.get(key).and_then(|o| o.as_i64()).unwrap_or(0).max(0) as u32;
+                        extracted.images.push(ImagePlacement {
+                            source: ImageSource::Inline(extracted.inline_images.len()),
+                            matrix: state.ctm,
+                            width: dimension(b"Width"),
+                            height: dimension(b"Height"),
+                            layer: marked_content.iter().rev().find_map(Clone::clone).or_else(|| base_layer.clone()),
+                        });
+                        extracted.inline_images.push(InlineImage { page: ctx.page, stream });
+                    }
+                }
                 "Do" => {
                     if let Some(res) = resources {
-                        if let Ok(xobjects) = res.get(b"XObject").and_then(|o| o.as_dict()) {
-                            if let Object::Name(name) = &op.operands[0] {
+                        if let Some(xobjects) = resource_category(doc, Some(res), b"XObject") {
+                            if let Some(Object::Name(name)) = op.operands.first() {
                                 if let Ok(Object::Reference(object_id)) = xobjects.get(name) {
                                     if let Ok(stream) = doc.get_object(*object_id).and_then(|o| o.as_stream()) {
                                         if let Ok(subtype) = stream.dict.get(b"Subtype").and_then(|o| o.as_name()) {
-                                            if subtype == b"Form" {
-                                                let mut form_ctm = current_ctm;
-                                                if let Ok(matrix_array) = stream.dict.get(b"Matrix").and_then(|o| o.as_array()) {
-                                                    if matrix_array.len() == 6 {
-                                                        let form_matrix = Transform {
-                                                            a: as_f64(&matrix_array[0]),
-                                                            b: as_f64(&matrix_array[1]),
-                                                            c: as_f64(&matrix_array[2]),
-                                                            d: as_f64(&matrix_array[3]),
-                                                            e: as_f64(&matrix_array[4]),
-                                                            f: as_f64(&matrix_array[5]),
-                                                        };
-                                                        form_ctm = form_ctm.multiply(&form_matrix);
-                                                    }
-                                                }
+                                            // Un formulaire qui s'appelle lui-même, directement ou non, n'est pas relu
+                                            if subtype == b"Form" && !visiting.contains(object_id) {
+                                                let form_matrix = match stream.dict.get(b"Matrix").and_then(|o| o.as_array()) {
+                                                    Ok(m) if m.len() == 6 => Transform {
+                                                        a: as_f64(&m[0]),
+                                                        b: as_f64(&m[1]),
+                                                        c: as_f64(&m[2]),
+                                                        d: as_f64(&m[3]),
+                                                        e: as_f64(&m[4]),
+                                                        f: as_f64(&m[5]),
+                                                    },
+                                                    _ => Transform::identity(),
+                                                };
+                                                // La BBox du formulaire découpe son contenu
+                                                let bbox_corners = |ctm: &Transform| -> Option<Vec<Point>> {
+                                                    let bbox = stream.dict.get(b"BBox").and_then(|o| o.as_array()).ok().filter(|bbox| bbox.len() == 4)?;
+                                                    let (x0, y0, x1, y1) = (as_f64(&bbox[0]), as_f64(&bbox[1]), as_f64(&bbox[2]), as_f64(&bbox[3]));
+                                                    Some([(x0, y0), (x1, y0), (x1, y1), (x0, y1)].iter().map(|&(x, y)| ctm.apply(Point { x, y })).collect())
+                                                };
+                                                let bbox_clip = |ctm: &Transform| bbox_corners(ctm).map(|corners| Rc::new(ClipRegion::from_polygon(corners)));
 
                                                 let mut form_resources = resources;
-                                                if let Ok(form_res) = stream.dict.get(b"Resources").and_then(|o| o.as_dict()) {
+                                                if let Some(form_res) = stream.dict.get(b"Resources").ok().and_then(|o| resolve_dict(doc, o)) {
                                                     form_resources = Some(form_res);
                                                 }
-                                                
-                                                if let Ok(form_data) = stream.decompressed_content() {
-                                                    parse_content_stream(doc, form_resources, &form_data, form_ctm, all_lines);
+
+                                                // Un formulaire peut appartenir lui-même à un calque (/OC)
+                                                let own_layer = stream.dict.get(b"OC").ok().and_then(|oc| optional_content.layer_for(doc, oc));
+                                                let enclosing_layer = marked_content.iter().rev().find_map(Clone::clone).or_else(|| base_layer.clone());
+
+                                                // Un formulaire sans /Filter n'est pas compressé : son contenu est lu tel quel
+                                                let form_data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+
+                                                // Formulaire répété : défini une seule fois, à la première rencontre, puis inséré à la CTM
+                                                // courante. Une CTM cisaillée, qu'une INSERT ne sait pas représenter, garde le formulaire
+                                                // recopié, tout comme un appel dont l'état hérité diffère de celui figé dans le bloc, ou dont
+                                                // la BBox déborde du découpage de l'appelant, qui ne s'applique pas au bloc.
+                                                let scale = state.ctm.scale();
+                                                let inherited = InheritedState {
+                                                    stroke_color: state.stroke_color,
+                                                    fill_color: state.fill_color,
+                                                    line_width: state.line_width,
+                                                    dash: state.dash.clone(),
+                                                    scale,
+                                                };
+                                                let existing = extracted.blocks.iter().position(|block| block.form == *object_id);
+                                                let reusable = existing.map_or(true, |block| extracted.blocks[block].inherited.matches(&inherited));
+                                                let within_clips = || match bbox_corners(&form_matrix.multiply(&state.ctm)) {
+                                                    Some(corners) => state.clip.iter().all(|clip| clip.contains_polygon(&corners)),
+                                                    None => state.clip.is_empty(),
+                                                };
+                                                if ctx.form_blocks.contains(object_id) && blocks::insert_placement(&state.ctm).is_some() && reusable && within_clips() {
+                                                    // Le bloc est défini à l'échelle de l'appel, pour que les épaisseurs DXF, que l'insertion
+                                                    // ne met pas à l'échelle, soient celles du dessin ; l'INSERT ne porte que le reste de la CTM
+                                                    let block_scale = Transform { a: scale, d: scale, ..Transform::identity() };
+                                                    let unscale = Transform { a: 1.0 / scale, d: 1.0 / scale, ..Transform::identity() };
+                                                    let block = match existing {
+                                                        Some(block) => block,
+                                                        None => {
+                                                            let definition_ctm = form_matrix.multiply(&block_scale);
+                                                            let mut definition_state = state.clone();
+                                                            definition_state.ctm = definition_ctm;
+                                                            definition_state.clip = bbox_clip(&definition_ctm).into_iter().collect();
+                                                            // Blocs imbriqués et images en ligne restent rangés dans le contenu principal
+                                                            let mut definition = PdfContent {
+                                                                blocks: std::mem::take(&mut extracted.blocks),
+                                                                inline_images: std::mem::take(&mut extracted.inline_images),
+                                                                ..Default::default()
+                                                            };
+                                                            // Sans calque propre, les entités du bloc restent sur le calque 0 et suivent celui de l'INSERT
+                                                            visiting.push(*object_id);
+                                                            parse_content_stream(ctx, form_resources, &form_data, definition_state, own_layer.clone(), visiting, &mut definition);
+                                                            visiting.pop();
+                                                            extracted.blocks = std::mem::take(&mut definition.blocks);
+                                                            extracted.inline_images = std::mem::take(&mut definition.inline_images);
+
+                                                            let name = blocks::block_name(name, |candidate| {
+                                                                extracted.blocks.iter().any(|block| block.name.eq_ignore_ascii_case(candidate))
+                                                            });
+                                                            extracted.blocks.push(FormBlock { form: *object_id, name, content: definition, inherited });
+                                                            extracted.blocks.len() - 1
+                                                        }
+                                                    };
+                                                    let matrix = unscale.multiply(&state.ctm);
+                                                    extracted.inserts.push(FormInsert { block, matrix, layer: enclosing_layer });
+                                                } else {
+                                                    let form_ctm = form_matrix.multiply(&state.ctm);
+                                                    let mut form_state = state.clone();
+                                                    form_state.ctm = form_ctm;
+                                                    form_state.clip.extend(bbox_clip(&form_ctm));
+                                                    visiting.push(*object_id);
+                                                    parse_content_stream(ctx, form_resources, &form_data, form_state, own_layer.or(enclosing_layer), visiting, extracted);
+                                                    visiting.pop();
                                                 }
+                                            } else if subtype == b"Image" {
+                                                let dimension = |key: &[u8]| stream.dict.get(key).and_then(|o| o.as_i64()).unwrap_or(0).max(0) as u32;
+                                                let layer = stream
+                                                    .dict
+                                                    .get(b"OC")
+                                                    .ok()
+                                                    .and_then(|oc| optional_content.layer_for(doc, oc))
+                                                    .or_else(|| marked_content.iter().rev().find_map(Clone::clone))
+                                                    .or_else(|| base_layer.clone());
+                                                extracted.images.push(ImagePlacement {
+                                                    source: ImageSource::XObject(*object_id),
+                                                    matrix: state.ctm,
+                                                    width: dimension(b"Width"),
+                                                    height: dimension(b"Height"),
+                                                    layer,
+                                                });
                                             }
                                         }
                                     }
@@ -238,150 +1156,1341 @@ fn parse_content_stream(
                         }
                     }
                 }
-                _ => {}
+                operator => {
+                    if let Some((close, stroke, fill)) = painting_operator(operator) {
+                        if close {
+                            current_subpath.close();
+                        }
+                        flush_subpath(&mut current_subpath, &mut current_path);
+                        let mut subpaths = std::mem::take(&mut current_path);
+                        if fill.is_some() {
+                            // Le remplissage ferme implicitement tous les sous-chemins
+                            subpaths.iter_mut().for_each(SubPath::close);
+                        }
+                        // Le nouveau découpage ne s'applique qu'aux chemins suivants
+                        let new_clip = pending_clip.take().map(|rule| Rc::new(ClipRegion::from_subpaths(&subpaths, rule)));
+
+                        let layer = marked_content.iter().rev().find_map(Clone::clone).or_else(|| base_layer.clone());
+                        extracted.paths.extend(state.painted_path(subpaths, stroke, fill, layer));
+                        if let Some(clip) = new_clip {
+                            state.clip.push(clip);
+                        }
+                        current_point = subpath_start;
+                    }
+                }
             }
         }
     }
 }
 
-pub fn extract_pdf_paths(doc: &Document) -> Vec<LineEntity> {
-    let mut all_lines = Vec::new();
-    let mut current_offset_x = 0.0;
-    let page_margin = 200.0;
+// Attribut héritable d'une page (/Resources, /MediaBox, /CropBox, /Rotate) : lu sur la page ou sur ses ancêtres /Pages
+fn inherited_attribute<'a>(doc: &'a Document, page: &'a lopdf::Dictionary, key: &[u8]) -> Option<&'a Object> {
+    let mut node = page;
+    // La profondeur est bornée pour ne pas boucler sur un arbre des pages circulaire
+    for _ in 0..64 {
+        if let Ok(value) = node.get(key) {
+            return Some(resolve(doc, value));
+        }
+        node = doc.get_dictionary(node.get(b"Parent").and_then(|o| o.as_reference()).ok()?).ok()?;
+    }
+    None
+}
 
-    for (_page_idx, (_, page_id)) in doc.get_pages().iter().enumerate() {
-        let mut page_width = 1000.0;
-        if let Ok(page_dict) = doc.get_dictionary(*page_id) {
-            if let Ok(media_box) = page_dict.get(b"MediaBox").and_then(|o| o.as_array()) {
-                if media_box.len() >= 4 {
-                    let llx = as_f64(&media_box[0]);
-                    let urx = as_f64(&media_box[2]);
-                    page_width = (urx - llx).abs();
-                }
+// Ressources d'une page, héritées du nœud /Pages le plus proche lorsque la page n'en déclare pas
+fn page_resources<'a>(doc: &'a Document, page: &'a lopdf::Dictionary) -> Option<&'a lopdf::Dictionary> {
+    inherited_attribute(doc, page, b"Resources")?.as_dict().ok()
+}
+
+// Catégorie de ressources (/XObject, /Font...), dictionnaire direct ou indirect
+fn resource_category<'a>(doc: &'a Document, resources: Option<&'a lopdf::Dictionary>, category: &[u8]) -> Option<&'a lopdf::Dictionary> {
+    resolve_dict(doc, resources?.get(category).ok()?)
+}
+
+// Rectangle [x0 y0 x1 y1] normalisé (coins inférieur gauche et supérieur droit)
+fn page_rectangle(doc: &Document, page: &lopdf::Dictionary, key: &[u8]) -> Option<[f64; 4]> {
+    let array = inherited_attribute(doc, page, key)?.as_array().ok()?;
+    if array.len() < 4 {
+        return None;
+    }
+    let v: Vec<f64> = array[..4].iter().map(|o| as_f64(resolve(doc, o))).collect();
+    Some([v[0].min(v[2]), v[1].min(v[3]), v[0].max(v[2]), v[1].max(v[3])])
+}
+
+// Géométrie d'une page : cadre d'extraction, rotation d'affichage et unité utilisateur
+struct PageGeometry {
+    bounds: [f64; 4],
+    // /Rotate ramené à 0, 90, 180 ou 270 (sens horaire)
+    rotate: i64,
+    // /UserUnit : taille de l'unité en points (PDF grand format)
+    user_unit: f64,
+}
+
+impl PageGeometry {
+    fn read(doc: &Document, page: &lopdf::Dictionary, page_box: PageBox) -> Self {
+        // A4 portrait à défaut de MediaBox
+        let media_box = page_rectangle(doc, page, b"MediaBox").unwrap_or([0.0, 0.0, 595.0, 842.0]);
+        let crop_box = page_rectangle(doc, page, b"CropBox").unwrap_or(media_box);
+        let chosen = match page_box {
+            PageBox::MediaBox => media_box,
+            PageBox::CropBox => crop_box,
+            // TrimBox et ArtBox ne sont pas héritables
+            PageBox::TrimBox | PageBox::ArtBox => page
+                .get(page_box.key())
+                .ok()
+                .and_then(|_| page_rectangle(doc, page, page_box.key()))
+                .unwrap_or(crop_box),
+        };
+        // Le cadre retenu est toujours limité à la MediaBox
+        let mut bounds = [
+            chosen[0].max(media_box[0]),
+            chosen[1].max(media_box[1]),
+            chosen[2].min(media_box[2]),
+            chosen[3].min(media_box[3]),
+        ];
+        if bounds[2] <= bounds[0] || bounds[3] <= bounds[1] {
+            bounds = media_box;
+        }
+
+        let rotate = inherited_attribute(doc, page, b"Rotate").and_then(|o| o.as_i64().ok()).unwrap_or(0);
+        let user_unit = page
+            .get(b"UserUnit")
+            .map(as_f64)
+            .ok()
+            .filter(|&unit| unit > 0.0)
+            .unwrap_or(1.0);
+        PageGeometry { bounds, rotate: (rotate / 90).rem_euclid(4) * 90, user_unit }
+    }
+
+    // Dimensions affichées (après rotation), en points de 1/72"
+    fn size(&self) -> (f64, f64) {
+        let (width, height) = (self.bounds[2] - self.bounds[0], self.bounds[3] - self.bounds[1]);
+        let (width, height) = if self.rotate % 180 == 0 { (width, height) } else { (height, width) };
+        (width * self.user_unit, height * self.user_unit)
+    }
+
+    // Transformation de base : coin du cadre ramené à l'origine, rotation horaire de /Rotate,
+    // unité utilisateur, puis décalage de la page dans la planche
+    fn base_transform(&self, (offset_x, offset_y): (f64, f64)) -> Transform {
+        let (width, height) = (self.bounds[2] - self.bounds[0], self.bounds[3] - self.bounds[1]);
+        let origin = Transform { e: -self.bounds[0], f: -self.bounds[1], ..Transform::identity() };
+        let rotation = match self.rotate {
+            90 => Transform { a: 0.0, b: -1.0, c: 1.0, d: 0.0, e: 0.0, f: width },
+            180 => Transform { a: -1.0, b: 0.0, c: 0.0, d: -1.0, e: width, f: height },
+            270 => Transform { a: 0.0, b: 1.0, c: -1.0, d: 0.0, e: height, f: 0.0 },
+            _ => Transform::identity(),
+        };
+        let user_unit = Transform { a: self.user_unit, d: self.user_unit, ..Transform::identity() };
+        let offset = Transform { e: offset_x, f: offset_y, ..Transform::identity() };
+        origin.multiply(&rotation).multiply(&user_unit).multiply(&offset)
+    }
+
+    // Découpage au cadre d'extraction, en coordonnées du dessin
+    fn clip(&self, base: &Transform) -> ClipRegion {
+        let [x0, y0, x1, y1] = self.bounds;
+        let corners = [(x0, y0), (x1, y0), (x1, y1), (x0, y1)].iter().map(|&(x, y)| base.apply(Point { x, y })).collect();
+        ClipRegion::from_polygon(corners)
+    }
+}
+
+// Appels `Do` de formulaires d'un flux de contenu, formulaires appelés compris, comptés comme `parse_content_stream`
+// les dessine : un formulaire écrit deux fois dans un formulaire lui-même appelé trois fois compte six fois.
+// `visiting` retient les formulaires en cours de lecture, pour ne pas boucler sur un formulaire qui s'appelle lui-même ;
+// `nested` garde les appels contenus dans chaque formulaire, lus une seule fois.
+fn count_form_uses(
+    doc: &Document,
+    resources: Option<&lopdf::Dictionary>,
+    content_data: &[u8],
+    visiting: &mut Vec<ObjectId>,
+    nested: &mut HashMap<ObjectId, Rc<HashMap<ObjectId, usize>>>,
+) -> HashMap<ObjectId, usize> {
+    let mut uses = HashMap::new();
+    let (content_data, _) = inline_images::split_inline_images(content_data);
+    let Ok(content) = Content::decode(&content_data) else {
+        return uses;
+    };
+    let Some(xobjects) = resource_category(doc, resources, b"XObject") else {
+        return uses;
+    };
+    for op in content.operations.iter().filter(|op| op.operator == "Do") {
+        let Some(Ok(Object::Reference(form_id))) = op.operands.first().and_then(|o| o.as_name().ok()).map(|name| xobjects.get(name)) else {
+            continue;
+        };
+        let Ok(stream) = doc.get_object(*form_id).and_then(|o| o.as_stream()) else {
+            continue;
+        };
+        if stream.dict.get(b"Subtype").and_then(|o| o.as_name()).ok() != Some(b"Form".as_slice()) || visiting.contains(form_id) {
+            continue;
+        }
+        *uses.entry(*form_id).or_insert(0usize) += 1;
+        let inner = match nested.get(form_id) {
+            Some(inner) => inner.clone(),
+            None => {
+                let form_resources = stream.dict.get(b"Resources").ok().and_then(|o| resolve_dict(doc, o)).or(resources);
+                let form_data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                visiting.push(*form_id);
+                let inner = Rc::new(count_form_uses(doc, form_resources, &form_data, visiting, nested));
+                visiting.pop();
+                nested.insert(*form_id, inner.clone());
+                inner
             }
+        };
+        for (&form, &count) in inner.iter() {
+            let total = uses.entry(form).or_insert(0);
+            *total = total.saturating_add(count);
         }
+    }
+    uses
+}
+
+// Décalage (coin inférieur gauche) de chaque page selon la disposition choisie. Les colonnes prennent la largeur
+// de leur plus grande page et les lignes sa hauteur : la première ligne repose sur l'axe X, les suivantes descendent.
+fn page_offsets(sizes: &[(f64, f64)], options: &ConversionOptions) -> Vec<(f64, f64)> {
+    let columns = match options.page_layout {
+        PageLayout::Strip => sizes.len(),
+        PageLayout::Grid => options.grid_columns,
+        PageLayout::Stack => 1,
+        PageLayout::Overlay | PageLayout::Separate | PageLayout::Layouts => return vec![(0.0, 0.0); sizes.len()],
+    }
+    .max(1);
+    let margin = options.page_margin.max(0.0);
 
-        let page_offset_x = current_offset_x;
-        current_offset_x += page_width + page_margin;
+    let mut column_widths = vec![0.0f64; columns];
+    let mut row_heights = vec![0.0f64; sizes.len().div_ceil(columns)];
+    for (i, &(width, height)) in sizes.iter().enumerate() {
+        column_widths[i % columns] = column_widths[i % columns].max(width);
+        row_heights[i / columns] = row_heights[i / columns].max(height);
+    }
+    let column_x: Vec<f64> = column_widths
+        .iter()
+        .scan(0.0, |x, width| {
+            let current = *x;
+            *x += width + margin;
+            Some(current)
+        })
+        .collect();
+    let row_y: Vec<f64> = row_heights
+        .iter()
+        .enumerate()
+        .scan(0.0, |y, (row, height)| {
+            if row > 0 {
+                *y -= height + margin;
+            }
+            Some(*y)
+        })
+        .collect();
+
+    (0..sizes.len()).map(|i| (column_x[i % columns], row_y[i / columns])).collect()
+}
 
-        if let Ok(content_data) = doc.get_page_content(*page_id) {
-            let page_dict = doc.get_dictionary(*page_id).ok();
-            let mut resources = None;
-            if let Some(dict) = page_dict {
-                if let Ok(res) = dict.get(b"Resources").and_then(|o| o.as_dict()) {
-                    resources = Some(res);
+// Extrait les pages demandées (numéros à partir de 1), disposées selon `options.page_layout`
+pub fn extract_pdf_content(doc: &Document, options: &ConversionOptions, page_numbers: &[u32]) -> PdfContent {
+    let mut extracted = PdfContent::default();
+    let mut ctx = ParseContext {
+        doc,
+        optional_content: OptionalContent::from_document(doc),
+        text_outlines: options.text_mode == TextMode::Outlines,
+        page: 0,
+        form_blocks: HashSet::new(),
+    };
+
+    let page_ids = doc.get_pages();
+    let labels = page_selection::page_labels(doc, page_ids.len());
+    let pages: Vec<(u32, ObjectId, &lopdf::Dictionary, PageGeometry)> = page_numbers
+        .iter()
+        .filter_map(|&page_number| {
+            let page_id = *page_ids.get(&page_number)?;
+            let page_dict = doc.get_dictionary(page_id).ok()?;
+            Some((page_number, page_id, page_dict, PageGeometry::read(doc, page_dict, options.page_box)))
+        })
+        .collect();
+    let sizes: Vec<(f64, f64)> = pages.iter().map(|(_, _, _, geometry)| geometry.size()).collect();
+    let offsets = page_offsets(&sizes, options);
+
+    if options.form_blocks {
+        let mut uses: HashMap<ObjectId, usize> = HashMap::new();
+        let mut nested = HashMap::new();
+        for &(_, page_id, page_dict, _) in &pages {
+            if let Ok(content_data) = doc.get_page_content(page_id) {
+                for (form, count) in count_form_uses(doc, page_resources(doc, page_dict), &content_data, &mut Vec::new(), &mut nested) {
+                    let total = uses.entry(form).or_insert(0);
+                    *total = total.saturating_add(count);
                 }
             }
-            
-            let mut base_page_ctm = Transform::identity();
-            base_page_ctm.e = page_offset_x;
+        }
+        ctx.form_blocks = uses.into_iter().filter(|&(_, count)| count > 1).map(|(form, _)| form).collect();
+    }
+
+    for (((page_number, page_id, page_dict, geometry), offset), size) in pages.into_iter().zip(offsets).zip(sizes) {
+        ctx.page = page_number;
+        extracted.pages.push(page_number);
+
+        if let Ok(content_data) = doc.get_page_content(page_id) {
+            let resources = page_resources(doc, page_dict);
+
+            let base_page_ctm = geometry.base_transform(offset);
+            let mut base_state = GraphicsState::new(base_page_ctm);
+            base_state.clip.push(Rc::new(geometry.clip(&base_page_ctm)));
 
             parse_content_stream(
-                doc,
+                &ctx,
                 resources,
                 &content_data,
-                base_page_ctm,
-                &mut all_lines,
+                base_state,
+                None,
+                &mut Vec::new(),
+                &mut extracted,
             );
         }
+
+        extracted.sheets.push(Sheet {
+            label: labels
+                .get(page_number as usize - 1)
+                .filter(|label| !label.is_empty())
+                .cloned()
+                .unwrap_or_else(|| page_number.to_string()),
+            size,
+            paths_end: extracted.paths.len(),
+            texts_end: extracted.texts.len(),
+            images_end: extracted.images.len(),
+            inserts_end: extracted.inserts.len(),
+        });
     }
 
-    all_lines
+    extracted
 }
 
-pub fn extract_images(doc: &Document, output_base_path: &Path) {
+// Images d'une page : XObjects de ses ressources, y compris ceux des formulaires imbriqués,
+// nommés d'après le chemin de ressources (« Fm0_Im1 »)
+fn collect_page_images(
+    doc: &Document,
+    resources: Option<&lopdf::Dictionary>,
+    prefix: &str,
+    visited_forms: &mut HashSet<ObjectId>,
+    found: &mut Vec<(String, ObjectId)>,
+) {
+    let Some(xobjects) = resource_category(doc, resources, b"XObject") else {
+        return;
+    };
+    for (name, object) in xobjects.iter() {
+        let Ok(object_id) = object.as_reference() else {
+            continue;
+        };
+        let Ok(stream) = doc.get_object(object_id).and_then(|o| o.as_stream()) else {
+            continue;
+        };
+        let resource_name = format!("{}{}", prefix, names::r12_name(&String::from_utf8_lossy(name)));
+        match stream.dict.get(b"Subtype").and_then(|o| o.as_name()) {
+            Ok(b"Image") => found.push((resource_name, object_id)),
+            // Un formulaire n'est parcouru qu'une fois par page, ce qui coupe aussi les références circulaires
+            Ok(b"Form") if visited_forms.insert(object_id) => {
+                let form_resources = stream.dict.get(b"Resources").ok().and_then(|o| resolve_dict(doc, o)).or(resources);
+                collect_page_images(doc, form_resources, &format!("{}_", resource_name), visited_forms, found);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Écrit une image sous `<dxf>_<nom>.<ext>`, sauf si une image de même contenu exporté l'a déjà été :
+// le fichier existant est alors réutilisé
+fn export_image(
+    doc: &Document,
+    stream: &Stream,
+    name: &str,
+    output_base_path: &Path,
+    files_by_content: &mut HashMap<(usize, u64), String>,
+) -> Option<String> {
     let base_name = output_base_path.file_stem().and_then(|s| s.to_str()).unwrap_or("document");
     let parent_dir = output_base_path.parent().unwrap_or(Path::new(""));
-    let mut image_counter = 1;
-
-    for (_object_id, object) in doc.objects.iter() {
-        if let Ok(stream) = object.as_stream() {
-            if let Ok(subtype) = stream.dict.get(b"Subtype").and_then(|o| o.as_name()) {
-                if subtype == b"Image" {
-                    if let Ok(filter) = stream.dict.get(b"Filter") {
-                        let is_jpeg = match filter {
-                            Object::Name(name) if name == b"DCTDecode" => true,
-                            Object::Array(arr) if arr.iter().any(|o| matches!(o, Object::Name(n) if n == b"DCTDecode")) => true,
-                            _ => false,
-                        };
+    let (data, extension) = match images::decode_image(doc, stream)? {
+        DecodedImage::Encoded { data, extension } => (data, extension),
+        DecodedImage::Raster(img) => {
+            let mut png = Vec::new();
+            img.write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png).ok()?;
+            (png, "png")
+        }
+    };
 
-                        if is_jpeg {
-                            let file_name = format!("{}_img_{}.jpg", base_name, image_counter);
-                            let file_path = parent_dir.join(&file_name);
-                            if let Ok(mut file) = std::fs::File::create(&file_path) {
-                                use std::io::Write;
-                                let _ = file.write_all(&stream.content);
-                            }
-                            image_counter += 1;
-                            continue;
-                        }
-                    }
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    let content_key = (data.len(), hasher.finish());
+    if let Some(file_name) = files_by_content.get(&content_key) {
+        return Some(file_name.clone());
+    }
 
-                    if let Ok(width) = stream.dict.get(b"Width").and_then(|o| o.as_i64()) {
-                        if let Ok(height) = stream.dict.get(b"Height").and_then(|o| o.as_i64()) {
-                            if let Ok(decompressed) = stream.decompressed_content() {
-                                let color_space = stream.dict.get(b"ColorSpace").and_then(|o| o.as_name()).unwrap_or(b"DeviceRGB");
-                                let bits_per_component = stream.dict.get(b"BitsPerComponent").and_then(|o| o.as_i64()).unwrap_or(8);
-
-                                if bits_per_component == 8 {
-                                    let is_rgb = color_space == b"DeviceRGB" || (decompressed.len() as i64 == width * height * 3);
-                                    let is_gray = color_space == b"DeviceGray" || (decompressed.len() as i64 == width * height);
-
-                                    let mut image_buffer = None;
-
-                                    if is_rgb && decompressed.len() as i64 >= width * height * 3 {
-                                        image_buffer = image::ImageBuffer::<image::Rgb<u8>, _>::from_raw(
-                                            width as u32,
-                                            height as u32,
-                                            decompressed[0..(width * height * 3) as usize].to_vec(),
-                                        ).map(|img| image::DynamicImage::ImageRgb8(img));
-                                    } else if is_gray && decompressed.len() as i64 >= width * height {
-                                        image_buffer = image::ImageBuffer::<image::Luma<u8>, _>::from_raw(
-                                            width as u32,
-                                            height as u32,
-                                            decompressed[0..(width * height) as usize].to_vec(),
-                                        ).map(|img| image::DynamicImage::ImageLuma8(img));
-                                    }
+    let file_name = format!("{}_{}.{}", base_name, name, extension);
+    std::fs::write(parent_dir.join(&file_name), &data).ok()?;
+    files_by_content.insert(content_key, file_name.clone());
+    Some(file_name)
+}
 
-                                    if let Some(img) = image_buffer {
-                                        let file_name = format!("{}_img_{}.png", base_name, image_counter);
-                                        let file_path = parent_dir.join(&file_name);
-                                        let _ = img.save(&file_path);
-                                        image_counter += 1;
-                                    }
-                                }
-                            }
-                        }
-                    }
+// Exporte les images utilisées par les pages extraites (ni vignettes ni objets orphelins), dans l'ordre des pages.
+// Les fichiers sont nommés d'après la page et la ressource (`inline1`, `inline2`... pour les images en ligne) ;
+// une image identique (même contenu exporté) utilisée sur plusieurs pages n'est écrite qu'une fois.
+pub fn extract_images(doc: &Document, content: &PdfContent, output_base_path: &Path) -> HashMap<ImageSource, String> {
+    let mut image_files = HashMap::new();
+    let mut files_by_content: HashMap<(usize, u64), String> = HashMap::new();
+
+    let page_ids = doc.get_pages();
+    for &page_number in &content.pages {
+        let Some(&page_id) = page_ids.get(&page_number) else {
+            continue;
+        };
+        let resources = doc.get_dictionary(page_id).ok().and_then(|page| page_resources(doc, page));
+        let mut found = Vec::new();
+        collect_page_images(doc, resources, "", &mut HashSet::new(), &mut found);
+
+        for (resource_name, object_id) in found {
+            let source = ImageSource::XObject(object_id);
+            if image_files.contains_key(&source) {
+                continue;
+            }
+            let Ok(stream) = doc.get_object(object_id).and_then(|o| o.as_stream()) else {
+                continue;
+            };
+            let name = format!("p{}_{}", page_number, resource_name);
+            if let Some(file_name) = export_image(doc, stream, &name, output_base_path, &mut files_by_content) {
+                image_files.insert(source, file_name);
+            }
+        }
+
+        let page_inline_images = content.inline_images.iter().enumerate().filter(|(_, inline)| inline.page == page_number);
+        for (number, (index, inline)) in page_inline_images.enumerate() {
+            let name = format!("p{}_inline{}", page_number, number + 1);
+            if let Some(file_name) = export_image(doc, &inline.stream, &name, output_base_path, &mut files_by_content) {
+                image_files.insert(ImageSource::Inline(index), file_name);
+            }
+        }
+    }
+
+    image_files
+}
+
+// Épaisseurs de ligne DXF normalisées (groupe 370, en centièmes de mm)
+const STANDARD_LINEWEIGHTS: [i16; 24] = [0, 5, 9, 13, 15, 18, 20, 25, 30, 35, 40, 50, 53, 60, 70, 80, 90, 100, 106, 120, 140, 158, 200, 211];
+
+// L'épaisseur est une épaisseur de tracé : elle reste celle de la feuille PDF, sans facteur d'échelle
+fn standard_lineweight(line_width_pt: f64) -> i16 {
+    let hundredths_mm = line_width_pt * 25.4 / 72.0 * 100.0;
+    STANDARD_LINEWEIGHTS
+        .iter()
+        .copied()
+        .min_by(|a, b| (*a as f64 - hundredths_mm).abs().total_cmp(&(*b as f64 - hundredths_mm).abs()))
+        .unwrap_or(0)
+}
+
+// Attributs communs appliqués à chaque entité DXF issue d'un même chemin
+struct EntityStyle {
+    color: Rgb,
+    layer: String,
+    lineweight: i16,
+    // Largeur constante des polylignes R12, en unités du dessin
+    polyline_width: f64,
+    // Type de ligne (LTYPE) des chemins en tirets, sinon BYLAYER
+    line_type: Option<String>,
+    // Le calque PDF d'origine est masqué par défaut
+    layer_off: bool,
+}
+
+impl EntityStyle {
+    fn for_path(path: &PathEntity, ctx: &DxfContext) -> Self {
+        let lineweight = if path.stroke { standard_lineweight(path.line_width) } else { 0 };
+        let r12_mode = if ctx.version == DxfVersion::R12 { ctx.line_width_r12 } else { R12LineWidth::None };
+        let ocg = path.layer.as_ref().map(|layer| layer.name.as_str());
+        EntityStyle {
+            color: if path.stroke { path.stroke_color } else { path.fill_color },
+            layer: match (r12_mode, ocg) {
+                (R12LineWidth::Layers, Some(name)) => format!("{}_LW_{:.2}", name, lineweight as f64 / 100.0),
+                (R12LineWidth::Layers, None) => format!("LW_{:.2}", lineweight as f64 / 100.0),
+                (_, Some(name)) => name.to_string(),
+                (_, None) => String::from("0"),
+            },
+            lineweight,
+            polyline_width: match r12_mode {
+                R12LineWidth::Polyline if path.stroke => path.line_width * ctx.final_scale,
+                _ => 0.0,
+            },
+            line_type: None,
+            layer_off: path.layer.as_ref().is_some_and(|layer| !layer.visible),
+        }
+    }
+
+    fn for_image(image: &ImagePlacement) -> Self {
+        EntityStyle {
+            color: Rgb::BLACK,
+            layer: image.layer.as_ref().map(|layer| layer.name.clone()).unwrap_or_else(|| String::from("0")),
+            lineweight: 0,
+            polyline_width: 0.0,
+            line_type: None,
+            layer_off: image.layer.as_ref().is_some_and(|layer| !layer.visible),
+        }
+    }
+
+    fn for_insert(insert: &FormInsert) -> Self {
+        EntityStyle {
+            color: Rgb::BLACK,
+            layer: insert.layer.as_ref().map(|layer| layer.name.clone()).unwrap_or_else(|| String::from("0")),
+            lineweight: 0,
+            polyline_width: 0.0,
+            line_type: None,
+            layer_off: insert.layer.as_ref().is_some_and(|layer| !layer.visible),
+        }
+    }
+
+    fn for_text(text: &TextEntity) -> Self {
+        EntityStyle {
+            color: text.color,
+            layer: text.layer.as_ref().map(|layer| layer.name.clone()).unwrap_or_else(|| String::from("0")),
+            lineweight: 0,
+            polyline_width: 0.0,
+            line_type: None,
+            layer_off: text.layer.as_ref().is_some_and(|layer| !layer.visible),
+        }
+    }
+}
+
+fn ensure_layer(drawing: &mut Drawing, style: &EntityStyle) {
+    if !drawing.layers().any(|layer| layer.name == style.layer) {
+        drawing.add_layer(Layer { name: style.layer.clone(), is_layer_on: !style.layer_off, ..Default::default() });
+    }
+}
+
+// Contexte d'écriture partagé par tous les sous-chemins
+struct DxfContext {
+    final_scale: f64,
+    tolerance_pt: f64,
+    use_splines: bool,
+    use_polylines: bool,
+    detect_arcs: bool,
+    version: DxfVersion,
+    line_width_r12: R12LineWidth,
+    // IMAGEDEF de chaque image, par origine
+    image_definitions: HashMap<ImageSource, dxf::Handle>,
+    // Nom DXF de chaque bloc, au rang de `PdfContent::blocks`
+    block_names: Vec<String>,
+}
+
+impl DxfContext {
+    fn point(&self, p: Point) -> dxf::Point {
+        dxf::Point::new(p.x * self.final_scale, p.y * self.final_scale, 0.0)
+    }
+}
+
+fn add_styled_entity(drawing: &mut Drawing, specific: dxf::entities::EntityType, style: &EntityStyle, ctx: &DxfContext) -> dxf::Handle {
+    let mut entity = Entity::new(specific);
+    entity.common.layer = style.layer.clone();
+    entity.common.lineweight_enum_value = style.lineweight;
+    if let Some(line_type) = &style.line_type {
+        entity.common.line_type_name = line_type.clone();
+    }
+    entity.common.color = dxf::Color::from_index(color::nearest_aci(style.color));
+    // Le noir et le blanc restent en ACI 7 pour s'adapter au fond d'écran du logiciel de CAO
+    if ctx.version.supports_true_color() && entity.common.color.index() != Some(7) {
+        entity.common.color_24_bit = style.color.true_color();
+    }
+    drawing.add_entity(entity).common.handle
+}
+
+fn add_line(drawing: &mut Drawing, start: Point, end: Point, style: &EntityStyle, ctx: &DxfContext) {
+    if (start.x - end.x).abs() > 0.001 || (start.y - end.y).abs() > 0.001 {
+        let dxf_line = Line::new(ctx.point(start), ctx.point(end));
+        add_styled_entity(drawing, dxf::entities::EntityType::Line(dxf_line), style, ctx);
+    }
+}
+
+fn add_bezier_spline(drawing: &mut Drawing, bezier: &BezierEntity, style: &EntityStyle, ctx: &DxfContext) {
+    // Une Bézier cubique est exactement une B-spline de degré 3 à nœuds [0,0,0,0,1,1,1,1]
+    let mut spline = Spline {
+        degree_of_curve: 3,
+        flags: 8, // planaire
+        normal: dxf::Vector::z_axis(),
+        knot_values: vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0],
+        ..Default::default()
+    };
+    for p in [bezier.p0, bezier.p1, bezier.p2, bezier.p3] {
+        spline.control_points.push(ctx.point(p));
+    }
+
+    add_styled_entity(drawing, dxf::entities::EntityType::Spline(spline), style, ctx);
+}
+
+fn add_arc(drawing: &mut Drawing, arc: &ArcEntity, style: &EntityStyle, ctx: &DxfContext) {
+    // Les arcs DXF sont toujours parcourus dans le sens trigonométrique
+    let (start_angle, end_angle) = if arc.sweep >= 0.0 {
+        (arc.start_angle, arc.start_angle + arc.sweep)
+    } else {
+        (arc.start_angle + arc.sweep, arc.start_angle)
+    };
+    let dxf_arc = Arc::new(ctx.point(arc.center), arc.radius * ctx.final_scale, start_angle.to_degrees(), end_angle.to_degrees());
+    add_styled_entity(drawing, dxf::entities::EntityType::Arc(dxf_arc), style, ctx);
+}
+
+fn add_circle(drawing: &mut Drawing, circle: &CircleEntity, style: &EntityStyle, ctx: &DxfContext) {
+    let dxf_circle = Circle::new(ctx.point(circle.center), circle.radius * ctx.final_scale);
+    add_styled_entity(drawing, dxf::entities::EntityType::Circle(dxf_circle), style, ctx);
+}
+
+// Sommet de polyligne : position et renflement (bulge) du segment qui en part
+struct PolyVertex {
+    point: Point,
+    bulge: f64,
+}
+
+fn add_polyline(drawing: &mut Drawing, vertices: &[PolyVertex], closed: bool, style: &EntityStyle, ctx: &DxfContext) {
+    // Le motif de tirets se poursuit d'un sommet à l'autre au lieu de repartir à chaque segment
+    let flags = (if closed { 1 } else { 0 }) | (if style.line_type.is_some() { 128 } else { 0 });
+    let specific = if ctx.version == DxfVersion::R12 {
+        let mut polyline = Polyline {
+            flags,
+            default_starting_width: style.polyline_width,
+            default_ending_width: style.polyline_width,
+            ..Default::default()
+        };
+        for v in vertices {
+            let vertex = Vertex {
+                location: ctx.point(v.point),
+                bulge: v.bulge,
+                ..Default::default()
+            };
+            polyline.add_vertex(drawing, vertex);
+        }
+        dxf::entities::EntityType::Polyline(polyline)
+    } else {
+        let mut lw_polyline = LwPolyline {
+            flags,
+            ..Default::default()
+        };
+        for v in vertices {
+            lw_polyline.vertices.push(LwPolylineVertex {
+                x: v.point.x * ctx.final_scale,
+                y: v.point.y * ctx.final_scale,
+                bulge: v.bulge,
+                ..Default::default()
+            });
+        }
+        dxf::entities::EntityType::LwPolyline(lw_polyline)
+    };
+
+    add_styled_entity(drawing, specific, style, ctx);
+}
+
+// Écrit une suite continue de droites et d'arcs : une seule polyligne, ou une entité simple s'il n'y a qu'un segment
+fn flush_run(drawing: &mut Drawing, run: &mut Vec<PathSegment>, closed: bool, style: &EntityStyle, ctx: &DxfContext) {
+    if run.is_empty() {
+        return;
+    }
+
+    // Une largeur de polyligne R12 impose la polyligne même pour un segment isolé
+    let simple_entities = style.polyline_width <= 0.0 && (!ctx.use_polylines || (run.len() == 1 && !closed));
+    if simple_entities {
+        for segment in run.iter() {
+            match segment {
+                PathSegment::Arc(arc) => add_arc(drawing, arc, style, ctx),
+                other => add_line(drawing, other.start(), other.end(), style, ctx),
+            }
+        }
+    } else {
+        let mut vertices: Vec<PolyVertex> = run
+            .iter()
+            .map(|segment| PolyVertex {
+                point: segment.start(),
+                bulge: match segment {
+                    // bulge = tan(angle balayé / 4), négatif dans le sens horaire
+                    PathSegment::Arc(arc) => (arc.sweep / 4.0).tan(),
+                    _ => 0.0,
+                },
+            })
+            .collect();
+        if !closed {
+            if let Some(last) = run.last() {
+                vertices.push(PolyVertex { point: last.end(), bulge: 0.0 });
+            }
+        }
+        add_polyline(drawing, &vertices, closed, style, ctx);
+    }
+
+    run.clear();
+}
+
+fn add_subpath(drawing: &mut Drawing, subpath: &SubPath, style: &EntityStyle, ctx: &DxfContext) {
+    let min_length = 0.001 / ctx.final_scale.abs().max(1e-9);
+    let mut run: Vec<PathSegment> = Vec::new();
+    // Une SPLINE ou un CIRCLE interrompt la polyligne, qui ne peut alors plus être fermée
+    let mut broken = false;
+
+    for segment in &subpath.segments {
+        match segment {
+            PathSegment::Line(line) => {
+                if (line.start.x - line.end.x).abs() > min_length || (line.start.y - line.end.y).abs() > min_length {
+                    run.push(segment.clone());
                 }
             }
+            PathSegment::Arc(_) => run.push(segment.clone()),
+            PathSegment::Bezier(bezier) if ctx.use_splines => {
+                flush_run(drawing, &mut run, false, style, ctx);
+                add_bezier_spline(drawing, bezier, style, ctx);
+                broken = true;
+            }
+            PathSegment::Bezier(bezier) => {
+                run.extend(bezier_to_lines(bezier, ctx.tolerance_pt).into_iter().map(PathSegment::Line));
+            }
+            PathSegment::Circle(circle) if style.polyline_width > 0.0 => {
+                // Cercle avec largeur R12 : polyligne fermée de deux demi-cercles (bulge = 1)
+                flush_run(drawing, &mut run, false, style, ctx);
+                let left = Point { x: circle.center.x - circle.radius, y: circle.center.y };
+                let vertices = [PolyVertex { point: segment.start(), bulge: 1.0 }, PolyVertex { point: left, bulge: 1.0 }];
+                add_polyline(drawing, &vertices, true, style, ctx);
+                broken = true;
+            }
+            PathSegment::Circle(circle) => {
+                flush_run(drawing, &mut run, false, style, ctx);
+                add_circle(drawing, circle, style, ctx);
+                broken = true;
+            }
+        }
+    }
+
+    flush_run(drawing, &mut run, subpath.closed && !broken, style, ctx);
+}
+
+// Contour de hachure d'un sous-chemin fermé : Béziers discrétisées, arcs et cercles conservés en renflements
+fn hatch_loop(subpath: &SubPath, ctx: &DxfContext) -> HatchLoop {
+    let scaled = |p: Point| Point { x: p.x * ctx.final_scale, y: p.y * ctx.final_scale };
+    let mut vertices = Vec::new();
+    for segment in &subpath.segments {
+        match segment {
+            PathSegment::Line(line) => vertices.push((scaled(line.start), 0.0)),
+            PathSegment::Arc(arc) => vertices.push((scaled(segment.start()), (arc.sweep / 4.0).tan())),
+            PathSegment::Bezier(bezier) => {
+                vertices.extend(bezier_to_lines(bezier, ctx.tolerance_pt).iter().map(|line| (scaled(line.start), 0.0)));
+            }
+            PathSegment::Circle(circle) => {
+                let left = Point { x: circle.center.x - circle.radius, y: circle.center.y };
+                vertices.push((scaled(segment.start()), 1.0));
+                vertices.push((scaled(left), 1.0));
+            }
+        }
+    }
+    // Les sommets confondus (segments nuls) sont retirés
+    vertices.dedup_by(|b, a| a.1 == 0.0 && (a.0.x - b.0.x).abs() < 1e-6 && (a.0.y - b.0.y).abs() < 1e-6);
+    HatchLoop { vertices }
+}
+
+// HATCH SOLID réservée par un POINT, remplacé à l'écriture du fichier (voir `dxf_patch`)
+fn add_hatch(drawing: &mut Drawing, hatches: &mut HashMap<u64, Vec<dxf::CodePair>>, loops: &[HatchLoop], style: &EntityStyle, ctx: &DxfContext) {
+    let placeholder = dxf::entities::EntityType::ModelPoint(ModelPoint::default());
+    let handle = add_styled_entity(drawing, placeholder, style, ctx);
+    hatches.insert(handle.0, hatch::hatch_pairs(loops));
+}
+
+// Une IMAGEDEF par fichier image, rangée dans le dictionnaire ACAD_IMAGE_DICT du dictionnaire racine
+fn add_image_definitions(
+    drawing: &mut Drawing,
+    root: dxf::Handle,
+    images: &[&ImagePlacement],
+    image_files: &HashMap<ImageSource, String>,
+) -> HashMap<ImageSource, dxf::Handle> {
+    let mut definitions = HashMap::new();
+    let mut entries = HashMap::new();
+    let image_dict = drawing.add_object(dxf::objects::Object::new(ObjectType::Dictionary(Dictionary::default()))).common.handle;
+
+    for image in images {
+        if definitions.contains_key(&image.source) {
+            continue;
+        }
+        if let Some(file_name) = image_files.get(&image.source) {
+            let definition = ImageDefinition {
+                file_path: file_name.clone(),
+                image_width: image.width as i32,
+                image_height: image.height as i32,
+                pixel_width: 1.0,
+                pixel_height: 1.0,
+                ..Default::default()
+            };
+            // Des images identiques partagent le même fichier, donc la même définition
+            let entry = Path::new(file_name).file_stem().and_then(|s| s.to_str()).unwrap_or(file_name).to_string();
+            let handle = match entries.get(&entry) {
+                Some(&handle) => handle,
+                None => drawing.add_object(dxf::objects::Object::new(ObjectType::ImageDefinition(definition))).common.handle,
+            };
+            entries.insert(entry, handle);
+            definitions.insert(image.source, handle);
+        }
+    }
+
+    for object in drawing.objects_mut() {
+        if let ObjectType::Dictionary(dict) = &mut object.specific {
+            if object.common.handle == root {
+                dict.value_handles.insert(String::from("ACAD_IMAGE_DICT"), image_dict);
+            } else if object.common.handle == image_dict {
+                dict.value_handles = std::mem::take(&mut entries);
+            }
+        }
+    }
+    definitions
+}
+
+// IMAGE : coin inférieur gauche et vecteurs d'un pixel en largeur (U) et en hauteur (V), d'après la matrice de placement
+fn add_image(drawing: &mut Drawing, image: &ImagePlacement, definition: dxf::Handle, style: &EntityStyle, ctx: &DxfContext) {
+    if image.width == 0 || image.height == 0 {
+        return;
+    }
+    let origin = image.matrix.apply(Point { x: 0.0, y: 0.0 });
+    let pixel_vector = |corner: Point, pixels: u32| {
+        dxf::Vector::new(
+            (corner.x - origin.x) / pixels as f64 * ctx.final_scale,
+            (corner.y - origin.y) / pixels as f64 * ctx.final_scale,
+            0.0,
+        )
+    };
+    let mut dxf_image = Image {
+        location: ctx.point(origin),
+        u_vector: pixel_vector(image.matrix.apply(Point { x: 1.0, y: 0.0 }), image.width),
+        v_vector: pixel_vector(image.matrix.apply(Point { x: 0.0, y: 1.0 }), image.height),
+        image_size: dxf::Vector::new(image.width as f64, image.height as f64, 0.0),
+        image_def_reference: definition.as_string(),
+        ..Default::default()
+    };
+    dxf_image.set_show_image(true);
+    dxf_image.set_show_image_when_not_alligned(true);
+    add_styled_entity(drawing, dxf::entities::EntityType::Image(dxf_image), style, ctx);
+}
+
+// TEXT en R12 ; MTEXT ensuite, sans largeur de cadre pour éviter tout retour à la ligne
+fn add_text(drawing: &mut Drawing, text: &TextEntity, style: &EntityStyle, ctx: &DxfContext) {
+    let height = text.height * ctx.final_scale.abs();
+    if height <= 0.0 {
+        return;
+    }
+    let specific = if ctx.version == DxfVersion::R12 {
+        dxf::entities::EntityType::Text(Text {
+            location: ctx.point(text.position),
+            text_height: height,
+            value: text.text.clone(),
+            rotation: text.rotation.to_degrees(),
+            relative_x_scale_factor: text.width_factor,
+            ..Default::default()
+        })
+    } else {
+        // Les caractères de contrôle MTEXT sont protégés ; la chasse passe par le code \W
+        let escaped = text.text.replace('\\', "\\\\").replace('{', "\\{").replace('}', "\\}");
+        let value = if (text.width_factor - 1.0).abs() > 1e-3 {
+            format!("\\W{:.3};{}", text.width_factor, escaped)
+        } else {
+            escaped
+        };
+        dxf::entities::EntityType::MText(MText {
+            insertion_point: ctx.point(text.position),
+            initial_text_height: height,
+            reference_rectangle_width: 0.0,
+            attachment_point: AttachmentPoint::BottomLeft,
+            x_axis_direction: dxf::Vector::new(text.rotation.cos(), text.rotation.sin(), 0.0),
+            rotation_angle: text.rotation.to_degrees(),
+            text: value,
+            ..Default::default()
+        })
+    };
+    add_styled_entity(drawing, specific, style, ctx);
+}
+
+// Éléments LTYPE (groupe 49) d'un motif de tirets : traits positifs, espaces négatifs, 0 pour un point
+fn dash_elements(dash: &DashPattern, final_scale: f64) -> Vec<f64> {
+    // Un tableau de longueur impaire est parcouru deux fois pour alterner traits et espaces
+    let mut lengths = dash.array.clone();
+    if lengths.len() % 2 == 1 {
+        lengths.extend_from_slice(&dash.array);
+    }
+    let total: f64 = lengths.iter().sum();
+
+    // Le décalage de départ est reporté en faisant tourner le motif
+    let mut offset = dash.phase.rem_euclid(total);
+    let mut head = Vec::new();
+    let mut tail = Vec::new();
+    for (i, &length) in lengths.iter().enumerate() {
+        let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+        if offset <= 0.0 {
+            head.push(sign * length);
+        } else if offset >= length {
+            tail.push(sign * length);
+            offset -= length;
+        } else {
+            head.push(sign * (length - offset));
+            tail.push(sign * offset);
+            offset = 0.0;
+        }
+    }
+    head.extend(tail);
+    head.into_iter().map(|v| v * final_scale.abs()).collect()
+}
+
+// Type de ligne correspondant au motif, créé au premier usage et partagé par les motifs identiques
+fn line_type_for(drawing: &mut Drawing, line_types: &mut HashMap<Vec<i64>, String>, dash: &DashPattern, ctx: &DxfContext) -> String {
+    let elements = dash_elements(dash, ctx.final_scale);
+    let key: Vec<i64> = elements.iter().map(|v| (v * 1e4).round() as i64).collect();
+    if let Some(name) = line_types.get(&key) {
+        return name.clone();
+    }
+
+    let name = format!("PDF_DASH_{}", line_types.len() + 1);
+    let description = elements
+        .iter()
+        .map(|&v| if v > 0.0 { "__" } else if v < 0.0 { " " } else { "." })
+        .collect::<String>();
+    drawing.add_line_type(LineType {
+        name: name.clone(),
+        description,
+        element_count: elements.len() as i32,
+        total_pattern_length: elements.iter().map(|v| v.abs()).sum(),
+        dash_dot_space_lengths: elements,
+        ..Default::default()
+    });
+    line_types.insert(key, name.clone());
+    name
+}
+
+// INSERT d'un bloc : point d'insertion, rotation et échelles décomposées depuis la CTM de l'appel `Do`
+fn add_insert(drawing: &mut Drawing, insert: &FormInsert, style: &EntityStyle, ctx: &DxfContext) {
+    let (Some(placement), Some(name)) = (blocks::insert_placement(&insert.matrix), ctx.block_names.get(insert.block)) else {
+        return;
+    };
+    let dxf_insert = Insert {
+        name: name.clone(),
+        location: ctx.point(Point { x: insert.matrix.e, y: insert.matrix.f }),
+        x_scale_factor: placement.x_scale,
+        y_scale_factor: placement.y_scale,
+        rotation: placement.rotation,
+        ..Default::default()
+    };
+    add_styled_entity(drawing, dxf::entities::EntityType::Insert(dxf_insert), style, ctx);
+}
+
+// Entités d'une partie du contenu : images sous les vecteurs, chemins, textes, puis insertions de blocs
+fn add_content(
+    drawing: &mut Drawing,
+    content: &PdfContent,
+    ranges: ContentRanges,
+    line_types: &mut HashMap<Vec<i64>, String>,
+    patches: &mut Patches,
+    ctx: &DxfContext,
+) {
+    // Les HATCH n'existent pas en R12 : les remplissages y restent de simples contours
+    let use_hatches = ctx.version != DxfVersion::R12;
+
+    // Les images (IMAGE, R14+) référencent les fichiers écrits à côté du DXF
+    for image in &content.images[ranges.images] {
+        if let Some(&definition) = ctx.image_definitions.get(&image.source) {
+            let style = EntityStyle::for_image(image);
+            ensure_layer(drawing, &style);
+            add_image(drawing, image, definition, &style, ctx);
+        }
+    }
+
+    for path in &content.paths[ranges.paths] {
+        let mut style = EntityStyle::for_path(path, ctx);
+        if let Some(dash) = path.dash.as_ref().filter(|_| path.stroke) {
+            style.line_type = Some(line_type_for(drawing, line_types, dash, ctx));
+        }
+        ensure_layer(drawing, &style);
+        let fitted = |subpaths: &[SubPath]| -> Vec<SubPath> {
+            if !ctx.detect_arcs {
+                return subpaths.to_vec();
+            }
+            subpaths
+                .iter()
+                .map(|subpath| SubPath {
+                    segments: arc_fitting::recognize_arcs(subpath.segments.clone(), ctx.tolerance_pt),
+                    closed: subpath.closed,
+                })
+                .collect()
+        };
+        let subpaths = fitted(&path.subpaths);
+
+        // La surface visible borde la hachure ; les morceaux ouverts d'un découpage non convexe restent en contour
+        let mut hatched = false;
+        if let Some(rule) = path.fill.filter(|_| use_hatches) {
+            let loops: Vec<HatchLoop> = fitted(&path.fill_area).iter().map(|sp| hatch_loop(sp, ctx)).collect();
+            let loops = hatch::boundary_loops(loops, rule);
+            if !loops.is_empty() {
+                let fill_style = EntityStyle { color: path.fill_color, lineweight: 0, line_type: None, ..EntityStyle::for_path(path, ctx) };
+                add_hatch(drawing, &mut patches.hatches, &loops, &fill_style, ctx);
+                hatched = true;
+            }
+        }
+
+        // Le contour d'un remplissage seul n'est plus dessiné une fois la hachure créée
+        for subpath in subpaths.iter().filter(|sp| path.stroke || !hatched || !sp.closed) {
+            add_subpath(drawing, subpath, &style, ctx);
         }
     }
+
+    for text in &content.texts[ranges.texts] {
+        let style = EntityStyle::for_text(text);
+        ensure_layer(drawing, &style);
+        add_text(drawing, text, &style, ctx);
+    }
+
+    for insert in &content.inserts[ranges.inserts] {
+        let style = EntityStyle::for_insert(insert);
+        ensure_layer(drawing, &style);
+        add_insert(drawing, insert, &style, ctx);
+    }
 }
 
-pub fn generate_dxf(lines: &[LineEntity], output_path: &str, scale_factor: f64, _unit: &str) -> io::Result<()> {
+pub fn generate_dxf(
+    content: &PdfContent,
+    image_files: &HashMap<ImageSource, String>,
+    output_path: &str,
+    scale_factor: f64,
+    _unit: &str,
+    options: &ConversionOptions,
+) -> io::Result<()> {
     let mut drawing = Drawing::new();
-    // Revert to R12 - it's the most compatible version and lacks complex header tags that can cause corruption if not perfectly formed
-    drawing.header.version = dxf::enums::AcadVersion::R12;
-    
+    // R12 par défaut : c'est la version la plus compatible. Les versions plus récentes sont nécessaires pour les SPLINE.
+    drawing.header.version = options.dxf_version.acad_version();
+
     // Convert PDF points (1/72 inch) to millimeters (25.4 mm / 72 pt)
     let pt_to_mm = 25.4 / 72.0;
     let final_scale = scale_factor * pt_to_mm;
 
-    for line in lines {
-        if (line.start.x - line.end.x).abs() > 0.001 || (line.start.y - line.end.y).abs() > 0.001 {
-            let p1 = dxf::Point::new(line.start.x * final_scale, line.start.y * final_scale, 0.0);
-            let p2 = dxf::Point::new(line.end.x * final_scale, line.end.y * final_scale, 0.0);
-            
-            let dxf_line = Line::new(p1, p2);
-            let mut entity = Entity::new(dxf::entities::EntityType::Line(dxf_line));
-            entity.common.layer = String::from("0");
-            drawing.add_entity(entity);
-        }
+    // La tolérance est exprimée en unités de sortie : on la ramène en points PDF
+    let tolerance = if options.tolerance > 0.0 { options.tolerance } else { ConversionOptions::default().tolerance };
+    let tolerance_pt = tolerance / final_scale.abs().max(1e-9);
+
+    let mut line_types: HashMap<Vec<i64>, String> = HashMap::new();
+    let mut patches = Patches::default();
+
+    // Les présentations (LAYOUT) n'existent pas en R12 : les pages y restent superposées dans l'espace objet
+    let use_layouts = options.page_layout == PageLayout::Layouts && options.dxf_version != DxfVersion::R12;
+    // Les images des blocs sont définies avec celles des pages
+    let images: Vec<&ImagePlacement> = content.images.iter().chain(content.blocks.iter().flat_map(|block| &block.content.images)).collect();
+    let use_images = options.dxf_version != DxfVersion::R12 && images.iter().any(|image| image_files.contains_key(&image.source));
+    // Le dictionnaire racine doit être le premier objet de la section OBJECTS
+    let root = (use_images || use_layouts)
+        .then(|| drawing.add_object(dxf::objects::Object::new(ObjectType::Dictionary(Dictionary::default()))).common.handle);
+    let image_definitions = match root {
+        Some(root) if use_images => add_image_definitions(&mut drawing, root, &images, image_files),
+        _ => HashMap::new(),
+    };
+
+    let ctx = DxfContext {
+        final_scale,
+        tolerance_pt,
+        use_splines: options.curve_mode == CurveMode::Spline && options.dxf_version.supports_splines(),
+        use_polylines: options.polylines,
+        detect_arcs: options.detect_arcs,
+        version: options.dxf_version,
+        line_width_r12: options.line_width_r12,
+        image_definitions,
+        block_names: content.blocks.iter().map(|block| block.name.clone()).collect(),
+    };
+
+    // Définitions des blocs : entités écrites comme les autres, puis déplacées dans leur BLOCK
+    for block in &content.blocks {
+        let start = drawing.entities().count();
+        add_content(&mut drawing, &block.content, block.content.all_ranges(), &mut line_types, &mut patches, &ctx);
+        let entities = blocks::take_entities(&mut drawing, start);
+        blocks::add_block(&mut drawing, &block.name, entities, false, &mut patches);
+    }
+
+    // Une feuille par présentation, ses entités à la suite ; sinon tout le contenu d'un seul tenant
+    let sheets = if use_layouts { content.sheet_ranges() } else { vec![content.all_ranges()] };
+    let mut sheet_starts = Vec::with_capacity(sheets.len());
+    for ranges in sheets {
+        sheet_starts.push(drawing.entities().count());
+        add_content(&mut drawing, content, ranges, &mut line_types, &mut patches, &ctx);
     }
 
-    match drawing.save_file(output_path) {
-        Ok(_) => Ok(()),
+    if let Some(root) = root.filter(|_| use_layouts) {
+        layouts::add_layouts(&mut drawing, root, &content.sheets, &sheet_starts, final_scale, &mut patches);
+    }
+
+    let mut buffer = Vec::new();
+    match drawing.save(&mut buffer) {
+        Ok(_) if patches.is_empty() => std::fs::write(output_path, buffer),
+        Ok(_) => std::fs::write(output_path, patches.apply(&String::from_utf8_lossy(&buffer))),
         Err(e) => Err(io::Error::new(io::ErrorKind::Other, format!("Failed to generate DXF: {:?}", e))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn point(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+
+    // Point de paramètre t de la Bézier [p0, p1, p2, p3]
+    fn cubic_point(p: [Point; 4], t: f64) -> Point {
+        let u = 1.0 - t;
+        let (b0, b1, b2, b3) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+        point(
+            b0 * p[0].x + b1 * p[1].x + b2 * p[2].x + b3 * p[3].x,
+            b0 * p[0].y + b1 * p[1].y + b2 * p[2].y + b3 * p[3].y,
+        )
+    }
+
+    fn distance_to_segment(p: Point, line: &LineEntity) -> f64 {
+        let (dx, dy) = (line.end.x - line.start.x, line.end.y - line.start.y);
+        let t = (((p.x - line.start.x) * dx + (p.y - line.start.y) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+        ((p.x - line.start.x - t * dx).powi(2) + (p.y - line.start.y - t * dy).powi(2)).sqrt()
+    }
+
+    #[test]
+    fn straight_cubic_is_a_single_segment() {
+        let mut lines = Vec::new();
+        flatten_bezier_rec(point(0.0, 0.0), point(1.0, 0.0), point(2.0, 0.0), point(3.0, 0.0), 0.01, 0, &mut lines);
+        assert_eq!(lines.len(), 1);
+        assert_eq!((lines[0].start.x, lines[0].end.x), (0.0, 3.0));
+    }
+
+    #[test]
+    fn flattened_curve_stays_within_tolerance() {
+        let curve = [point(0.0, 0.0), point(0.0, 50.0), point(100.0, 50.0), point(100.0, 0.0)];
+        let mut lines = Vec::new();
+        flatten_bezier_rec(curve[0], curve[1], curve[2], curve[3], 0.1, 0, &mut lines);
+        assert!(lines.len() > 1);
+        assert!(lines.windows(2).all(|w| w[0].end.x == w[1].start.x && w[0].end.y == w[1].start.y));
+        for i in 0..=200 {
+            let p = cubic_point(curve, i as f64 / 200.0);
+            let deviation = lines.iter().map(|line| distance_to_segment(p, line)).fold(f64::INFINITY, f64::min);
+            assert!(deviation <= 0.1, "écart {deviation} au paramètre {i}/200");
+        }
+    }
+
+    #[test]
+    fn subdivision_stops_at_max_depth() {
+        let mut lines = Vec::new();
+        flatten_bezier_rec(point(0.0, 0.0), point(0.0, 50.0), point(100.0, 50.0), point(100.0, 0.0), 0.0, MAX_FLATTEN_DEPTH - 2, &mut lines);
+        assert_eq!(lines.len(), 4);
+    }
+
+    fn context(version: DxfVersion) -> DxfContext {
+        DxfContext {
+            final_scale: 1.0,
+            tolerance_pt: 0.1,
+            use_splines: false,
+            use_polylines: true,
+            detect_arcs: false,
+            version,
+            line_width_r12: R12LineWidth::None,
+            image_definitions: HashMap::new(),
+            block_names: Vec::new(),
+        }
+    }
+
+    fn style() -> EntityStyle {
+        EntityStyle { color: Rgb::BLACK, layer: String::from("0"), lineweight: 0, polyline_width: 0.0, line_type: None, layer_off: false }
+    }
+
+    fn line(a: (f64, f64), b: (f64, f64)) -> PathSegment {
+        PathSegment::Line(LineEntity { start: point(a.0, a.1), end: point(b.0, b.1) })
+    }
+
+    // Polylignes R2000 écrites pour une suite de segments : fermeture et renflement de chaque sommet
+    fn polylines(mut run: Vec<PathSegment>, closed: bool) -> Vec<(bool, Vec<f64>)> {
+        let mut drawing = Drawing::new();
+        flush_run(&mut drawing, &mut run, closed, &style(), &context(DxfVersion::R2000));
+        drawing
+            .entities()
+            .filter_map(|entity| match &entity.specific {
+                dxf::entities::EntityType::LwPolyline(polyline) => {
+                    Some((polyline.is_closed(), polyline.vertices.iter().map(|v| v.bulge).collect()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn runs_become_closed_or_open_polylines() {
+        let square = vec![
+            line((0.0, 0.0), (10.0, 0.0)),
+            line((10.0, 0.0), (10.0, 10.0)),
+            line((10.0, 10.0), (0.0, 10.0)),
+            line((0.0, 10.0), (0.0, 0.0)),
+        ];
+        assert_eq!(polylines(square, true), vec![(true, vec![0.0; 4])]);
+
+        // Tracé ouvert : le dernier point est ajouté en sommet final
+        let open = vec![line((0.0, 0.0), (10.0, 0.0)), line((10.0, 0.0), (10.0, 10.0))];
+        assert_eq!(polylines(open, false), vec![(false, vec![0.0; 3])]);
+    }
+
+    #[test]
+    fn arc_bulge_follows_the_direction() {
+        let quarter = |sweep: f64| PathSegment::Arc(ArcEntity { center: point(0.0, 0.0), radius: 10.0, start_angle: 0.0, sweep });
+        let expected = (std::f64::consts::FRAC_PI_2 / 4.0).tan();
+
+        // Sens trigonométrique : de (10, 0) à (0, 10), renflement positif
+        let ccw = polylines(vec![quarter(std::f64::consts::FRAC_PI_2), line((0.0, 10.0), (10.0, 0.0))], true);
+        assert_eq!(ccw.len(), 1);
+        assert!((ccw[0].1[0] - expected).abs() < 1e-9);
+        assert_eq!(ccw[0].1[1], 0.0);
+
+        // Sens horaire : de (10, 0) à (0, -10), renflement négatif
+        let cw = polylines(vec![quarter(-std::f64::consts::FRAC_PI_2), line((0.0, -10.0), (10.0, 0.0))], true);
+        assert!((cw[0].1[0] + expected).abs() < 1e-9);
+    }
+
+    // Document d'une page A4 dont le flux de contenu est `content`
+    fn document(content: &[u8]) -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let content_id = doc.add_object(lopdf::Stream::new(dictionary! {}, content.to_vec()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {"Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1}));
+        let catalog_id = doc.add_object(dictionary! {"Type" => "Catalog", "Pages" => pages_id});
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    fn paths(doc: &Document) -> Vec<PathEntity> {
+        extract_pdf_content(doc, &ConversionOptions::default(), &[1]).paths
+    }
+
+    #[test]
+    fn painting_operators_give_stroke_and_fill_rule() {
+        assert_eq!(painting_operator("S"), Some((false, true, None)));
+        assert_eq!(painting_operator("f*"), Some((false, false, Some(FillRule::EvenOdd))));
+        assert_eq!(painting_operator("B*"), Some((false, true, Some(FillRule::EvenOdd))));
+        assert_eq!(painting_operator("b"), Some((true, true, Some(FillRule::NonZero))));
+        assert_eq!(painting_operator("n"), None);
+        assert_eq!(painting_operator("W"), None);
+    }
+
+    #[test]
+    fn only_painted_paths_are_recorded() {
+        // Le chemin de découpage terminé par `n` n'est pas peint ; `B*` trace et remplit en pair-impair
+        let doc = document(b"q 10 10 m 100 10 l 100 100 l W n Q 0 0 m 50 50 l S 20 20 80 80 re B* 0 0 m 10 0 l");
+        let paths = paths(&doc);
+        assert_eq!(paths.len(), 2);
+
+        assert!(paths[0].stroke);
+        assert_eq!(paths[0].fill, None);
+        assert_eq!(paths[0].subpaths.len(), 1);
+        assert!(!paths[0].subpaths[0].closed);
+
+        assert!(paths[1].stroke);
+        assert_eq!(paths[1].fill, Some(FillRule::EvenOdd));
+        assert_eq!(paths[1].subpaths.len(), 1);
+        assert!(paths[1].subpaths[0].closed);
+    }
+
+    #[test]
+    fn line_widths_snap_to_standard_lineweights() {
+        // 1 pt = 0,353 mm ; 0,5 pt = 0,176 mm
+        assert_eq!(standard_lineweight(1.0), 35);
+        assert_eq!(standard_lineweight(0.5), 18);
+        assert_eq!(standard_lineweight(0.7), 25);
+        assert_eq!(standard_lineweight(20.0), 211);
+        // Trait d'épaisseur nulle : le plus fin possible
+        assert_eq!(standard_lineweight(0.0), 0);
+    }
+
+    #[test]
+    fn marked_content_paths_keep_their_layer() {
+        let mut doc = document(b"/OC /MC0 BDC 0 0 m 10 10 l S EMC 0 0 m 10 0 l S");
+        let ocg = doc.add_object(dictionary! {"Type" => "OCG", "Name" => Object::string_literal("Cotes")});
+        let properties = dictionary! {"OCGs" => vec![ocg.into()], "D" => dictionary! {"OFF" => vec![ocg.into()]}};
+        let catalog_id = doc.trailer.get(b"Root").and_then(Object::as_reference).unwrap();
+        doc.get_dictionary_mut(catalog_id).unwrap().set("OCProperties", properties);
+        let page_id = doc.get_pages()[&1];
+        let resources = dictionary! {"Properties" => dictionary! {"MC0" => ocg}};
+        doc.get_dictionary_mut(page_id).unwrap().set("Resources", resources);
+
+        let paths = paths(&doc);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].layer, Some(ContentLayer { name: String::from("Cotes"), visible: false }));
+        assert_eq!(paths[1].layer, None);
+    }
+
+    #[test]
+    fn self_referencing_form_is_read_once_per_call() {
+        // Le formulaire se rappelle lui-même par ses propres ressources
+        let mut doc = document(b"/Fm0 Do /Fm0 Do");
+        let form_id = doc.new_object_id();
+        let form = lopdf::Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), 100.into(), 100.into()],
+                "Resources" => dictionary! {"XObject" => dictionary! {"Fm0" => form_id}},
+            },
+            b"0 0 m 10 0 l S /Fm0 Do".to_vec(),
+        );
+        doc.objects.insert(form_id, Object::Stream(form));
+        let page_id = doc.get_pages()[&1];
+        doc.get_dictionary_mut(page_id).unwrap().set("Resources", dictionary! {"XObject" => dictionary! {"Fm0" => form_id}});
+
+        assert_eq!(paths(&doc).len(), 2);
+        let options = ConversionOptions { form_blocks: true, ..Default::default() };
+        let content = extract_pdf_content(&doc, &options, &[1]);
+        assert_eq!(content.blocks.len(), 1);
+        assert_eq!(content.blocks[0].content.paths.len(), 1);
+        assert_eq!(content.inserts.len(), 2);
+    }
+
+    #[test]
+    fn dash_elements_alternate_dashes_and_gaps() {
+        let dash = |array: &[f64], phase: f64| DashPattern { array: array.to_vec(), phase };
+        assert_eq!(dash_elements(&dash(&[6.0, 3.0], 0.0), 2.0), vec![12.0, -6.0]);
+        // Le décalage de départ fait tourner le motif
+        assert_eq!(dash_elements(&dash(&[6.0, 3.0], 2.0), 1.0), vec![4.0, -3.0, 2.0]);
+        assert_eq!(dash_elements(&dash(&[6.0, 3.0], 7.0), 1.0), vec![-2.0, 6.0, -1.0]);
+        // Un tableau impair est parcouru deux fois
+        assert_eq!(dash_elements(&dash(&[4.0], 0.0), 1.0), vec![4.0, -4.0]);
+        assert_eq!(dash_elements(&dash(&[5.0, 2.0, 3.0], 0.0), 1.0), vec![5.0, -2.0, 3.0, -5.0, 2.0, -3.0]);
+    }
+
+    // Coins du cadre d'extraction transformés, arrondis au point
+    fn corners(geometry: &PageGeometry) -> Vec<(f64, f64)> {
+        let base = geometry.base_transform((0.0, 0.0));
+        let [x0, y0, x1, y1] = geometry.bounds;
+        [(x0, y0), (x1, y0), (x1, y1), (x0, y1)]
+            .iter()
+            .map(|&(x, y)| {
+                let p = base.apply(Point { x, y });
+                (p.x.round(), p.y.round())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn base_transform_rotates_the_page_box_to_the_origin() {
+        // CropBox décalée de 100 × 200 pt ; les coins sont donnés dans l'ordre bas-gauche, bas-droit, haut-droit, haut-gauche
+        let page = |rotate: i64| PageGeometry { bounds: [10.0, 20.0, 110.0, 220.0], rotate, user_unit: 1.0 };
+        assert_eq!(corners(&page(0)), vec![(0.0, 0.0), (100.0, 0.0), (100.0, 200.0), (0.0, 200.0)]);
+        assert_eq!(corners(&page(90)), vec![(0.0, 100.0), (0.0, 0.0), (200.0, 0.0), (200.0, 100.0)]);
+        assert_eq!(corners(&page(180)), vec![(100.0, 200.0), (0.0, 200.0), (0.0, 0.0), (100.0, 0.0)]);
+        assert_eq!(corners(&page(270)), vec![(200.0, 0.0), (200.0, 100.0), (0.0, 100.0), (0.0, 0.0)]);
+        assert_eq!(page(90).size(), (200.0, 100.0));
+    }
+
+    #[test]
+    fn base_transform_applies_user_unit() {
+        let page = PageGeometry { bounds: [10.0, 20.0, 110.0, 220.0], rotate: 90, user_unit: 2.5 };
+        assert_eq!(corners(&page), vec![(0.0, 250.0), (0.0, 0.0), (500.0, 0.0), (500.0, 250.0)]);
+        assert_eq!(page.size(), (500.0, 250.0));
+    }
+
+    #[test]
+    fn page_offsets_for_each_layout() {
+        let sizes = [(100.0, 200.0), (300.0, 50.0), (150.0, 120.0)];
+        let offsets = |page_layout: PageLayout| {
+            let options = ConversionOptions { page_layout, grid_columns: 2, page_margin: 10.0, ..Default::default() };
+            page_offsets(&sizes, &options)
+        };
+        assert_eq!(offsets(PageLayout::Strip), vec![(0.0, 0.0), (110.0, 0.0), (420.0, 0.0)]);
+        // Colonnes aussi larges que leur page la plus large, lignes aussi hautes que leur page la plus haute
+        assert_eq!(offsets(PageLayout::Grid), vec![(0.0, 0.0), (160.0, 0.0), (0.0, -130.0)]);
+        assert_eq!(offsets(PageLayout::Stack), vec![(0.0, 0.0), (0.0, -60.0), (0.0, -190.0)]);
+        assert_eq!(offsets(PageLayout::Overlay), vec![(0.0, 0.0); 3]);
+    }
+}
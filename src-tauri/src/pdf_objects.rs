@@ -0,0 +1,27 @@
+use lopdf::{Dictionary, Document, Object};
+
+// Lecture des objets PDF partagée par les modules d'extraction
+
+// Objet désigné par une référence, ou l'objet lui-même
+pub fn resolve<'a>(doc: &'a Document, obj: &'a Object) -> &'a Object {
+    doc.dereference(obj).map(|(_, o)| o).unwrap_or(obj)
+}
+
+// Dictionnaire direct ou désigné par une référence
+pub fn resolve_dict<'a>(doc: &'a Document, obj: &'a Object) -> Option<&'a Dictionary> {
+    resolve(doc, obj).as_dict().ok()
+}
+
+// Nombre PDF, entier ou réel
+pub fn number(obj: &Object) -> Option<f64> {
+    match obj {
+        Object::Integer(i) => Some(*i as f64),
+        Object::Real(f) => Some(*f as f64),
+        _ => None,
+    }
+}
+
+// Nombre PDF, 0 pour tout autre objet
+pub fn as_f64(obj: &Object) -> f64 {
+    number(obj).unwrap_or(0.0)
+}
@@ -0,0 +1,209 @@
+use crate::color::Rgb;
+use crate::font::{Font, Glyph};
+use crate::optional_content::ContentLayer;
+use crate::pdf_converter::{Point, Transform};
+use crate::pdf_objects;
+use lopdf::Object;
+use std::rc::Rc;
+
+// Au-delà de cet écart (en millièmes de corps) dans un tableau `TJ`, un espace est inséré
+const WORD_GAP_THRESHOLD: f64 = 250.0;
+
+// Chaîne de texte affichée par `Tj`, `TJ`, `'` ou `"`, en coordonnées de page
+#[derive(Debug, Clone)]
+pub struct TextEntity {
+    pub text: String,
+    // Point d'insertion sur la ligne de base
+    pub position: Point,
+    // Hauteur du corps, transformée par la matrice de texte et la CTM
+    pub height: f64,
+    // Orientation de la ligne de base, en radians
+    pub rotation: f64,
+    // Rapport largeur / hauteur (`Tz`, matrices anisotropes)
+    pub width_factor: f64,
+    pub color: Rgb,
+    pub layer: Option<ContentLayer>,
+}
+
+// Résultat d'un opérateur d'affichage : texte décodé, matrice de rendu de la chaîne et de chaque glyphe
+pub struct ShownText {
+    pub text: String,
+    pub matrix: Transform,
+    pub glyphs: Vec<(Glyph, Transform)>,
+}
+
+// Paramètres de texte de l'état graphique (sauvegardés par `q` / `Q`)
+#[derive(Debug, Clone)]
+pub struct TextState {
+    pub font: Option<Rc<Font>>,
+    pub size: f64,
+    pub char_spacing: f64,
+    pub word_spacing: f64,
+    // `Tz` en fraction (1.0 = 100 %)
+    pub horizontal_scaling: f64,
+    pub leading: f64,
+    pub rise: f64,
+    pub render_mode: i64,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        TextState {
+            font: None,
+            size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            render_mode: 0,
+        }
+    }
+}
+
+impl TextState {
+    // Modes 3 (invisible, ex. couche OCR) et 7 (découpage seul) : rien n'est dessiné
+    pub fn is_visible(&self) -> bool {
+        !matches!(self.render_mode, 3 | 7)
+    }
+
+    // Modes 1 et 5 : texte uniquement tracé en contour
+    pub fn uses_stroke_color(&self) -> bool {
+        matches!(self.render_mode, 1 | 5)
+    }
+
+    // Contours de glyphes : (tracé, remplissage) selon le mode de rendu
+    pub fn outline_painting(&self) -> (bool, bool) {
+        (matches!(self.render_mode, 1 | 2 | 5 | 6), matches!(self.render_mode, 0 | 2 | 4 | 6))
+    }
+}
+
+// Matrices de texte d'un objet `BT` ... `ET`
+#[derive(Debug, Clone, Copy)]
+pub struct TextObject {
+    matrix: Transform,
+    line_matrix: Transform,
+}
+
+impl Default for TextObject {
+    fn default() -> Self {
+        TextObject { matrix: Transform::identity(), line_matrix: Transform::identity() }
+    }
+}
+
+impl TextObject {
+    // `Td` (et `TD`, `T*`) : début de ligne suivante, relatif au début de la ligne courante
+    pub fn move_line(&mut self, tx: f64, ty: f64) {
+        let translation = Transform { e: tx, f: ty, ..Transform::identity() };
+        self.line_matrix = translation.multiply(&self.line_matrix);
+        self.matrix = self.line_matrix;
+    }
+
+    // `Tm`
+    pub fn set_matrix(&mut self, matrix: Transform) {
+        self.line_matrix = matrix;
+        self.matrix = matrix;
+    }
+
+    // Affiche un tableau `TJ` (chaînes et décalages en millièmes de corps) et avance la matrice de texte
+    pub fn show(&mut self, state: &TextState, ctm: &Transform, items: &[Object]) -> Option<ShownText> {
+        let font = state.font.as_ref()?;
+        // Matrice de rendu : corps, mise à l'échelle horizontale et élévation, puis matrice de texte et CTM
+        let font_matrix = Transform {
+            a: state.size * state.horizontal_scaling,
+            b: 0.0,
+            c: 0.0,
+            d: state.size,
+            e: 0.0,
+            f: state.rise,
+        };
+        let origin = self.matrix;
+        let mut text = String::new();
+        let mut glyphs = Vec::new();
+
+        for item in items {
+            match item {
+                Object::String(bytes, _) => {
+                    for glyph in font.decode(bytes) {
+                        let spacing = state.char_spacing + if glyph.is_space { state.word_spacing } else { 0.0 };
+                        let glyph_matrix = font_matrix.multiply(&self.matrix).multiply(ctm);
+                        self.advance((glyph.width * state.size + spacing) * state.horizontal_scaling);
+                        text.push_str(&glyph.text);
+                        glyphs.push((glyph, glyph_matrix));
+                    }
+                }
+                Object::Integer(_) | Object::Real(_) => {
+                    let adjustment = pdf_objects::as_f64(item);
+                    self.advance(-adjustment / 1000.0 * state.size * state.horizontal_scaling);
+                    if -adjustment > WORD_GAP_THRESHOLD && !text.is_empty() && !text.ends_with(' ') {
+                        text.push(' ');
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if glyphs.is_empty() {
+            return None;
+        }
+        Some(ShownText {
+            text: text.trim_end().to_string(),
+            matrix: font_matrix.multiply(&origin).multiply(ctm),
+            glyphs,
+        })
+    }
+
+    fn advance(&mut self, tx: f64) {
+        self.matrix = Transform { e: tx, ..Transform::identity() }.multiply(&self.matrix);
+    }
+}
+
+impl TextEntity {
+    // Entité de texte à partir de la matrice de rendu (corps unitaire)
+    pub fn from_render_matrix(text: String, matrix: &Transform, color: Rgb, layer: Option<ContentLayer>) -> Self {
+        let x_length = (matrix.a * matrix.a + matrix.b * matrix.b).sqrt();
+        let height = (matrix.c * matrix.c + matrix.d * matrix.d).sqrt();
+        TextEntity {
+            text,
+            position: matrix.apply(Point { x: 0.0, y: 0.0 }),
+            height,
+            rotation: matrix.b.atan2(matrix.a),
+            width_factor: if height > 0.0 { x_length / height } else { 1.0 },
+            color,
+            layer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    // Police sans /Widths : chasse de 0,5 corps par glyphe
+    fn state() -> TextState {
+        let mut doc = lopdf::Document::with_version("1.5");
+        let font = doc.add_object(dictionary! {"Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica"});
+        let resources = dictionary! {"Font" => dictionary! {"F1" => font}};
+        let font = Font::load(&doc, Some(&resources), b"F1").unwrap();
+        TextState { font: Some(Rc::new(font)), size: 10.0, ..Default::default() }
+    }
+
+    #[test]
+    fn tj_array_applies_spacing_scaling_and_adjustments() {
+        // Tc 1, Tw 4, Tz 50 %, Ts 2 ; la chaîne commence en (100, 50)
+        let state = TextState { char_spacing: 1.0, word_spacing: 4.0, horizontal_scaling: 0.5, rise: 2.0, ..state() };
+        let mut object = TextObject::default();
+        object.set_matrix(Transform { e: 100.0, f: 50.0, ..Transform::identity() });
+        let items = [Object::string_literal("A B"), Object::Integer(-500), Object::string_literal("C")];
+
+        let shown = object.show(&state, &Transform::identity(), &items).unwrap();
+        // Un décalage TJ supérieur à un quart de corps sépare deux mots
+        assert_eq!(shown.text, "A B C");
+        assert_eq!((shown.matrix.a, shown.matrix.d, shown.matrix.e, shown.matrix.f), (5.0, 10.0, 100.0, 52.0));
+        // (5 + 1) / 2 par glyphe, + 4 / 2 pour l'espace, + 500 / 1000 × 10 / 2 pour l'ajustement
+        let origins: Vec<(f64, f64)> = shown.glyphs.iter().map(|(_, m)| (m.e, m.f)).collect();
+        assert_eq!(origins, vec![(100.0, 52.0), (103.0, 52.0), (108.0, 52.0), (113.5, 52.0)]);
+        assert_eq!(object.matrix.e, 116.5);
+    }
+}